@@ -81,21 +81,42 @@ DockedPanel {
 
         IconButton {
             id: playPauseButton
+
+            // request ID of the play or pause command in flight, 0 if none
+            property var pendingRequest: 0
+
             anchors {
                 top: albumArt.top
                 right: parent.right
                 bottom: albumArt.bottom
             }
 
+            enabled: pendingRequest === 0
             icon.source: librespot.playbackState === SailifyPlayer.Paused
                          ? "image://theme/icon-m-play"
                          : "image://theme/icon-m-pause"
+            icon.opacity: enabled ? 1.0 : 0.0
 
             onClicked: {
                 if (librespot.playbackState === SailifyPlayer.Playing) {
-                    librespot.pause()
+                    pendingRequest = librespot.pause()
                 } else {
-                    librespot.play()
+                    pendingRequest = librespot.play()
+                }
+            }
+
+            BusyIndicator {
+                anchors.centerIn: parent
+                size: BusyIndicatorSize.Small
+                running: playPauseButton.pendingRequest !== 0
+            }
+
+            Connections {
+                target: librespot
+                onCommandResult: {
+                    if (requestId === playPauseButton.pendingRequest) {
+                        playPauseButton.pendingRequest = 0
+                    }
                 }
             }
         }
@@ -3,9 +3,43 @@ import org.freedesktop.contextkit 1.0
 
 Object {
     property bool online: networkState.value === "connected"
+    property bool metered: online && networkType.value !== "WLAN"
+                           && networkType.value !== "ethernet"
+
+    // Emitted when going on- or offline or when switching networks,
+    // but not when the initial values arrive
+    signal networkChanged()
+
+    property string _lastState
+    property string _lastType
+
+    function _update() {
+        var state = networkState.value || ""
+        var type = networkType.value || ""
+        if (state === "") {
+            return
+        }
+
+        var changed = _lastState !== ""
+            && (state !== _lastState || (type !== "" && _lastType !== "" && type !== _lastType))
+        _lastState = state
+        if (type !== "") {
+            _lastType = type
+        }
+        if (changed) {
+            networkChanged()
+        }
+    }
 
     ContextProperty {
         id: networkState
         key: "Internet.NetworkState"
+        onValueChanged: _update()
+    }
+
+    ContextProperty {
+        id: networkType
+        key: "Internet.NetworkType"
+        onValueChanged: _update()
     }
 }
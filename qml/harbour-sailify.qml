@@ -1,5 +1,6 @@
 import QtQuick 2.0
 import Sailfish.Silica 1.0
+import Nemo.Notifications 1.0
 import Sailify 0.1
 import "pages"
 import "components"
@@ -18,6 +19,17 @@ ApplicationWindow
 
     SailifyPlayer {
         id: librespot
+
+        onCommandResult: {
+            if (status !== SailifyPlayer.CommandCompleted) {
+                commandNotification.previewSummary = message
+                commandNotification.publish()
+            }
+        }
+    }
+
+    Notification {
+        id: commandNotification
     }
 
     CurrentlyPlayingPanel {
@@ -48,6 +60,7 @@ ApplicationWindow
 
     NetworkMonitor {
         id: networkMonitor
+        onNetworkChanged: librespot.networkChanged(online, metered)
     }
 
     // Commands
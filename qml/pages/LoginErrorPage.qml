@@ -1,10 +1,38 @@
 import QtQuick 2.0
 import Sailfish.Silica 1.0
+import Sailify 0.1
 
 Page {
     id: page
 
     property bool allowLogout: true
+    property bool premiumRequired: librespot.errorKind === SailifyPlayer.PremiumRequired
+    property bool retryable: !premiumRequired
+                             && librespot.errorKind !== SailifyPlayer.BadCredentials
+                             && librespot.errorKind !== SailifyPlayer.AccountRestricted
+
+    function advice() {
+        switch (librespot.errorKind) {
+        case SailifyPlayer.PremiumRequired:
+            return qsTr("Sailify can only play music with a Spotify Premium account. "
+                        + "Upgrade your account or log in with another one.")
+        case SailifyPlayer.BadCredentials:
+            return qsTr("The username or password is wrong. Log out and log in again.")
+        case SailifyPlayer.NetworkUnreachable:
+            return qsTr("Spotify can not be reached. Check your internet connection and proxy settings.")
+        case SailifyPlayer.AccessPointRefused:
+            return qsTr("The Spotify server refused the connection. Try again in a moment.")
+        case SailifyPlayer.Timeout:
+            return qsTr("Spotify did not answer in time. Check your internet connection and try again.")
+        case SailifyPlayer.RateLimited:
+            return qsTr("Too many login attempts. Wait a few minutes and try again.")
+        case SailifyPlayer.AccountRestricted:
+            return qsTr("Spotify does not allow this account to log in here. "
+                        + "Check your account on the Spotify website.")
+        default:
+            return ""
+        }
+    }
 
     SilicaFlickable {
         id: flickable
@@ -30,14 +58,30 @@ Page {
                     left: parent.left
                     leftMargin: Theme.horizontalPageMargin
                 }
-                text: librespot.errorString || qsTr("Unknown error")
+                text: advice() || librespot.errorString || qsTr("Unknown error")
 
                 color: Theme.secondaryHighlightColor
                 wrapMode: Text.Wrap
             }
 
+            Label {
+                anchors {
+                    right: parent.right
+                    rightMargin: Theme.horizontalPageMargin
+                    left: parent.left
+                    leftMargin: Theme.horizontalPageMargin
+                }
+                visible: advice() !== "" && librespot.errorString !== ""
+                text: librespot.errorString
+
+                color: Theme.secondaryColor
+                font.pixelSize: Theme.fontSizeSmall
+                wrapMode: Text.Wrap
+            }
+
             Button {
                text: qsTr("Retry")
+               visible: retryable
                anchors {
                    horizontalCenter: parent.horizontalCenter
                }
@@ -1,5 +1,6 @@
 import QtQuick 2.0
 import Sailfish.Silica 1.0
+import Sailify 0.1
 
 Page {
     id: page
@@ -9,15 +10,48 @@ Page {
     function login(user, pass) {
         librespot.username = usernameField.text
         librespot.password = passwordField.text
+        passwordField.text = ""
+        librespot.stop()
         pageStack.push(Qt.resolvedUrl("LoginProgressPage.qml"), {}, PageStackAction.Immediate)
     }
 
+    function loginWithBrowser() {
+        var url = librespot.startOAuthLogin()
+        if (url) {
+            Qt.openUrlExternally(url)
+        }
+    }
+
+    Connections {
+        target: librespot
+
+        onOauthLoginCompleted: {
+            if (pageStack.currentPage === page) {
+                librespot.stop()
+                pageStack.push(
+                    Qt.resolvedUrl("LoginProgressPage.qml"), {}, PageStackAction.Immediate)
+            }
+        }
+
+        onRemoteLogin: {
+            if (pageStack.currentPage === page) {
+                pageStack.push(
+                    Qt.resolvedUrl("LoginProgressPage.qml"), {}, PageStackAction.Immediate)
+            }
+        }
+    }
+
     Connections {
         target: pageStack
 
         onCurrentPageChanged: {
-            if (pageStack.currentPage === page) {
+            if (pageStack.currentPage === page
+                    && librespot.connectionStatus !== SailifyPlayer.WaitingForDiscovery) {
                 librespot.logout()
+                if (librespot.discovery) {
+                    // wait for a Connect app to hand over a login
+                    librespot.start()
+                }
             }
         }
     }
@@ -70,6 +104,12 @@ Page {
                         usernameField.text,
                         passwordField.text)
             }
+
+            Button {
+                text: qsTr("Log in with browser")
+                anchors.horizontalCenter: parent.horizontalCenter
+                onClicked: loginWithBrowser()
+            }
         }
 
         VerticalScrollDecorator { }
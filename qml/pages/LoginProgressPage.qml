@@ -15,6 +15,7 @@ Page {
             progressLabel.text = qsTr("Connecting …")
             break;
         case SailifyPlayer.Connected:
+        case SailifyPlayer.WaitingForDiscovery:
             onComplete()
             break;
         }
@@ -25,6 +26,7 @@ Page {
 
         onConnectionStatusChanged: onComplete()
         onErrorOccurred: onComplete()
+        onLoginStageChanged: progressLabel.text = stageText(librespot.loginStage)
     }
 
     Timer {
@@ -41,15 +43,32 @@ Page {
                 onError()
                 break;
             case SailifyPlayer.Connecting:
-                progressLabel.text = qsTr("Connecting …")
+                progressLabel.text = stageText(librespot.loginStage)
                 break;
             case SailifyPlayer.Connected:
                 pageStack.replaceAbove(null, Qt.resolvedUrl("MainNavigationPage.qml"))
                 break;
+            case SailifyPlayer.WaitingForDiscovery:
+                // the login page shows while a Connect app can hand over a login
+                pageStack.replace(Qt.resolvedUrl("LoginPage.qml"), {}, PageStackAction.Immediate)
+                break;
             }
         }
     }
 
+    function stageText(stage) {
+        switch (stage) {
+        case SailifyPlayer.Authenticating:
+            return qsTr("Logging in …")
+        case SailifyPlayer.StartingSpirc:
+            return qsTr("Starting player …")
+        case SailifyPlayer.FetchingToken:
+            return qsTr("Loading your library …")
+        default:
+            return qsTr("Connecting …")
+        }
+    }
+
     function onComplete() {
         pageStack.completeAnimation()
         changeTimer.start()
@@ -60,7 +79,10 @@ Page {
         if (librespot.errorKind === SailifyPlayer.MissingCredentials) {
             pageStack.replace(Qt.resolvedUrl("LoginPage.qml"), {}, PageStackAction.Immediate)
         } else {
-            pageStack.replace(Qt.resolvedUrl("LoginErrorPage.qml"), { allowLogout: false }, PageStackAction.Immediate)
+            var wrongAccount = librespot.errorKind === SailifyPlayer.PremiumRequired
+                    || librespot.errorKind === SailifyPlayer.BadCredentials
+                    || librespot.errorKind === SailifyPlayer.AccountRestricted
+            pageStack.replace(Qt.resolvedUrl("LoginErrorPage.qml"), { allowLogout: wrongAccount }, PageStackAction.Immediate)
         }
     }
 
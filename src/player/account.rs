@@ -0,0 +1,97 @@
+//! Account information
+//!
+//! librespot does not expose the user attributes the access point sends, so the product type is
+//! read from the Web API with the access token of the session.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use url::Url;
+
+const ME_URL: &str = "https://api.spotify.com/v1/me";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccountProduct {
+    Unknown = 0,
+    Free = 1,
+    Premium = 2,
+}
+
+impl AccountProduct {
+    fn parse(value: &str) -> Self {
+        match value {
+            "premium" => AccountProduct::Premium,
+            "free" | "open" => AccountProduct::Free,
+            _ => AccountProduct::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AccountInfo {
+    pub username: String,
+    pub display_name: Option<String>,
+    /// ISO 3166-1 alpha-2 country code
+    pub country: String,
+    pub product: AccountProduct,
+}
+
+impl AccountInfo {
+    /// Whether librespot can play music for the account
+    ///
+    /// Accounts of an unknown product are given the benefit of the doubt.
+    #[must_use]
+    pub fn can_stream(&self) -> bool {
+        self.product != AccountProduct::Free
+    }
+}
+
+#[derive(Deserialize)]
+struct MeResponse {
+    id: String,
+    display_name: Option<String>,
+    #[serde(default)]
+    country: Option<String>,
+    #[serde(default)]
+    product: Option<String>,
+}
+
+/// Request the account information of the owner of `access_token`
+///
+/// `country` of the session is used when the token may not read the country. Blocks until the
+/// request finished.
+pub fn fetch_account_info(
+    access_token: &str,
+    proxy: Option<&Url>,
+    country: String,
+) -> Result<AccountInfo, String> {
+    let mut agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT);
+    if let Some(proxy) = proxy {
+        let proxy = ureq::Proxy::new(proxy.as_str()).map_err(|err| err.to_string())?;
+        agent = agent.proxy(proxy);
+    }
+
+    let response: MeResponse = agent
+        .build()
+        .get(ME_URL)
+        .set("Authorization", &format!("Bearer {}", access_token))
+        .call()
+        .map_err(|err| format!("Account request failed: {}", err))?
+        .into_json()
+        .map_err(|err| format!("Invalid account response: {}", err))?;
+
+    Ok(AccountInfo {
+        username: response.id,
+        display_name: response.display_name.filter(|name| !name.is_empty()),
+        country: response
+            .country
+            .filter(|country| !country.is_empty())
+            .unwrap_or(country),
+        product: response
+            .product
+            .as_deref()
+            .map_or(AccountProduct::Unknown, AccountProduct::parse),
+    })
+}
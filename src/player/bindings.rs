@@ -1,14 +1,26 @@
 //! C bindings for SailifyPlayer
+//!
+//! A layer over [`PlayerHandle`], which is the opaque `SailifyPlayer` of the C API. Events go to
+//! the callbacks given to `sailify_player_new` and `sailify_player_subscribe`.
 
 use std::env;
 use std::ffi::c_void;
 use std::marker::PhantomData;
 use std::os::raw::c_char;
 use std::sync::Arc;
+use std::time::Duration;
 
+use librespot_playback::config::{AudioFormat, Bitrate};
+
+use crate::player::account::AccountProduct;
+use crate::player::commands::CommandError;
 use crate::player::error::LibrespotError;
-use crate::player::events::{LibrespotEvent, LibrespotEventListener};
-use crate::player::SailifyPlayer;
+use crate::player::events::{EventCategories, LibrespotEvent, LibrespotEventListener};
+use crate::player::handle::PlayerHandle;
+use crate::player::login::LoginStage;
+use crate::player::state::StateSnapshot;
+use crate::player::volume::raw_to_percent;
+use crate::player::{ConnectionStatus, MediaStatus, PlayerState, RepeatMode, Setting};
 
 #[repr(C)]
 #[derive(Clone)]
@@ -93,25 +105,35 @@ impl<'a> ToInternal for SailifyStringView<'a> {
     }
 }
 
-fn string_to_ffi(s: &str) -> SailifyStringView {
+fn string_to_ffi(s: &str) -> SailifyStringView<'_> {
     SailifyStringView::from(s)
 }
 
+/// Comma separated names of `settings`
+fn setting_names(settings: &[Setting]) -> String {
+    settings
+        .iter()
+        .map(|setting| setting.name())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 // SailifyString
 
+/// String owned by the caller, freed with `sailify_string_delete`
 pub struct SailifyString {
-    __private: [u8; 0],
+    value: String,
 }
 
-impl<'a> IntoFfi for String {
+impl IntoFfi for String {
     type Ffi = *mut SailifyString;
 
     fn into_ffi(self) -> *mut SailifyString {
-        Box::into_raw(self.into_boxed_str()).cast::<SailifyString>()
+        Box::into_raw(Box::new(SailifyString { value: self }))
     }
 }
 
-impl<'a> IntoFfi for Option<String> {
+impl IntoFfi for Option<String> {
     type Ffi = *mut SailifyString;
 
     fn into_ffi(self) -> *mut SailifyString {
@@ -122,6 +144,45 @@ impl<'a> IntoFfi for Option<String> {
     }
 }
 
+// SailifyPlayerState
+
+#[repr(C)]
+pub struct SailifyPlayerState<'a> {
+    pub player_state: PlayerState,
+    pub media_status: MediaStatus,
+    pub connection_status: ConnectionStatus,
+    pub track_uri: SailifyStringView<'a>,
+    /// Only valid if `has_play_request_id` is set
+    pub play_request_id: u64,
+    pub has_play_request_id: bool,
+    pub position_ms: u32,
+    pub duration_ms: u32,
+    /// Volume in percent (0-100)
+    pub volume: u16,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+}
+
+impl<'a> ToFfi for &'a StateSnapshot {
+    type Ffi = SailifyPlayerState<'a>;
+
+    fn to_ffi(&self) -> SailifyPlayerState<'a> {
+        SailifyPlayerState {
+            player_state: self.player_state,
+            media_status: self.media_status,
+            connection_status: self.connection_status,
+            track_uri: self.track_uri.as_deref().to_ffi(),
+            play_request_id: self.play_request_id.unwrap_or(0),
+            has_play_request_id: self.play_request_id.is_some(),
+            position_ms: self.position_ms,
+            duration_ms: self.duration_ms,
+            volume: raw_to_percent(self.volume),
+            shuffle: self.shuffle,
+            repeat: self.repeat,
+        }
+    }
+}
+
 // General
 
 #[no_mangle]
@@ -134,110 +195,681 @@ pub extern "C" fn sailify_init() {
         .try_init();
 }
 
+#[no_mangle]
+pub extern "C" fn sailify_string_get(this: &SailifyString) -> SailifyStringView<'_> {
+    string_to_ffi(&this.value)
+}
+
+/// Free a string, null is ignored
 #[no_mangle]
 pub unsafe extern "C" fn sailify_string_delete(this: *mut SailifyString) {
-    Box::from_raw(this);
+    if !this.is_null() {
+        drop(Box::from_raw(this));
+    }
+}
+
+// SailifyState
+
+/// Snapshot of the player state owned by the caller, freed with `sailify_state_delete`
+pub struct SailifyState {
+    snapshot: StateSnapshot,
+}
+
+#[no_mangle]
+pub extern "C" fn sailify_state_get(this: &SailifyState) -> SailifyPlayerState<'_> {
+    (&this.snapshot).to_ffi()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_state_delete(this: *mut SailifyState) {
+    if !this.is_null() {
+        drop(Box::from_raw(this));
+    }
 }
 
 // SailifyPlayer
 
 #[no_mangle]
-pub extern "C" fn sailify_player_new(callbacks: &SailifyCallback) -> *mut SailifyPlayer {
-    Box::into_raw(Box::new(SailifyPlayer::new(Arc::new(callbacks.clone()))))
+pub extern "C" fn sailify_player_new(callbacks: &SailifyCallback) -> *mut PlayerHandle {
+    Box::into_raw(Box::new(PlayerHandle::with_listener(Arc::new(
+        callbacks.clone(),
+    ))))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_delete(this: *mut PlayerHandle) {
+    drop(Box::from_raw(this));
+}
+
+/// Register more callbacks for the events in `categories` (`*_EVENTS` flags)
+///
+/// The latest track, connection and volume events are replayed before this returns. `destroy` of
+/// `callbacks` is called after `sailify_player_unsubscribe` or with the player.
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_subscribe(
+    this: &PlayerHandle,
+    callbacks: &SailifyCallback,
+    categories: EventCategories,
+) -> u64 {
+    this.subscribe(Arc::new(callbacks.clone()), categories)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_unsubscribe(
+    this: &PlayerHandle,
+    subscription: u64,
+) -> bool {
+    this.unsubscribe(subscription)
+}
+
+/// Deliver events only from `sailify_player_poll_events` instead of a dispatch thread
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_event_polling(this: &PlayerHandle, enabled: bool) {
+    this.set_event_polling(enabled);
+}
+
+/// Call the callbacks for up to `max_events` queued events on the calling thread
+///
+/// Returns the number of delivered events.
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_poll_events(
+    this: &PlayerHandle,
+    max_events: usize,
+) -> usize {
+    this.poll_events(max_events)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_play(this: &PlayerHandle) -> u64 {
+    this.with_player(|player| player.play())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_pause(this: &PlayerHandle) -> u64 {
+    this.with_player(|player| player.pause())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_next(this: &PlayerHandle) -> u64 {
+    this.with_player(|player| player.next())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_previous(this: &PlayerHandle) -> u64 {
+    this.with_player(|player| player.previous())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_seek(this: &PlayerHandle, position_ms: u32) -> u64 {
+    this.with_player(|player| player.seek(position_ms))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_volume(this: &PlayerHandle, percent: u16) -> u64 {
+    this.with_player(|player| player.set_volume(percent))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_load(
+    this: &PlayerHandle,
+    context_uri: SailifyStringView,
+    track_uris: *const SailifyStringView,
+    track_uris_len: usize,
+    start_index: u32,
+    position_ms: u32,
+    shuffle: bool,
+) -> u64 {
+    let track_uris = if track_uris.is_null() {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(track_uris, track_uris_len)
+            .iter()
+            .filter_map(|uri| uri.to_internal().map(ToString::to_string))
+            .collect()
+    };
+    this.with_player(|player| {
+        player.load(
+            context_uri.to_internal().unwrap_or(""),
+            track_uris,
+            start_index,
+            position_ms,
+            shuffle,
+        )
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_shuffle(this: &PlayerHandle, shuffle: bool) -> u64 {
+    this.with_player(|player| player.set_shuffle(shuffle))
+}
+
+/// Set the repeat mode, a `RepeatMode` value
+///
+/// Invalid values, like 2 for repeating a single track which the Spirc doesn't support, are
+/// reported as rejected command.
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_repeat(this: &PlayerHandle, repeat: u32) -> u64 {
+    this.with_player(|player| match RepeatMode::from_raw(repeat) {
+        Some(repeat) => player.set_repeat(repeat),
+        None => player.reject_command("set-repeat", format!("Invalid repeat mode {}", repeat)),
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_volume_up(this: &PlayerHandle) -> u64 {
+    this.with_player(|player| player.volume_up())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_volume_down(this: &PlayerHandle) -> u64 {
+    this.with_player(|player| player.volume_down())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_stop(this: &PlayerHandle) {
+    this.with_player(|player| player.stop());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_start(this: &PlayerHandle) {
+    this.with_player(|player| player.start());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_logout(this: &PlayerHandle) {
+    this.with_player(|player| player.logout());
 }
 
+/// Start an OAuth login and return the URL to open in a browser
+///
+/// Null when the login could not be started.
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_delete(this: *mut SailifyPlayer) {
-    Box::from_raw(this);
+pub unsafe extern "C" fn sailify_player_start_oauth_login(
+    this: &PlayerHandle,
+) -> *mut SailifyString {
+    this.with_player(|player| player.start_oauth_login())
+        .into_ffi()
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_play(this: &mut SailifyPlayer) {
-    this.play();
+pub unsafe extern "C" fn sailify_player_cancel_oauth_login(this: &PlayerHandle) {
+    this.with_player(|player| player.cancel_oauth_login());
 }
 
+/// Comma separated names of all profiles
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_pause(this: &mut SailifyPlayer) {
-    this.pause();
+pub unsafe extern "C" fn sailify_player_get_profiles(this: &PlayerHandle) -> *mut SailifyString {
+    this.with_player(|player| player.profile_names()).into_ffi()
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_next(this: &mut SailifyPlayer) {
-    this.next();
+pub unsafe extern "C" fn sailify_player_get_active_profile(
+    this: &PlayerHandle,
+) -> *mut SailifyString {
+    this.with_player(|player| player.active_profile().to_string())
+        .into_ffi()
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_previous(this: &mut SailifyPlayer) {
-    this.previous();
+pub unsafe extern "C" fn sailify_player_create_profile(
+    this: &PlayerHandle,
+    name: SailifyStringView,
+) -> bool {
+    this.with_player(|player| player.create_profile(name.to_internal().unwrap_or("")))
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_stop(this: &mut SailifyPlayer) {
-    this.stop();
+pub unsafe extern "C" fn sailify_player_delete_profile(
+    this: &PlayerHandle,
+    name: SailifyStringView,
+) -> bool {
+    this.with_player(|player| player.delete_profile(name.to_internal().unwrap_or("")))
 }
 
+/// Shut down the player and start it again with the credentials of the profile if it was running
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_start(this: &mut SailifyPlayer) {
-    this.start();
+pub unsafe extern "C" fn sailify_player_switch_profile(
+    this: &PlayerHandle,
+    name: SailifyStringView,
+) -> bool {
+    this.with_player(|player| player.switch_profile(name.to_internal().unwrap_or("")))
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_logout(this: &mut SailifyPlayer) {
-    this.logout();
+pub unsafe extern "C" fn sailify_player_apply_settings(this: &PlayerHandle) -> bool {
+    this.with_player(|player| player.apply_settings())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_save_settings(this: &PlayerHandle) -> bool {
+    this.with_player(|player| player.save_settings())
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn sailify_player_set_username(
-    this: &mut SailifyPlayer,
+    this: &PlayerHandle,
     username: SailifyStringView,
 ) {
-    this.set_username(username.to_internal());
+    this.with_player(|player| player.set_username(username.to_internal()));
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_get_username(
-    this: &mut SailifyPlayer,
-) -> SailifyStringView {
-    this.username().to_ffi()
+pub unsafe extern "C" fn sailify_player_get_username(this: &PlayerHandle) -> *mut SailifyString {
+    this.with_player(|player| player.username().map(ToString::to_string))
+        .into_ffi()
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn sailify_player_set_password(
-    this: &mut SailifyPlayer,
+    this: &PlayerHandle,
     password: SailifyStringView,
 ) {
-    this.set_password(password.to_internal());
+    this.with_player(|player| player.set_password(password.to_internal()));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_proxy(
+    this: &PlayerHandle,
+    proxy: SailifyStringView,
+) -> bool {
+    this.with_player(|player| player.set_proxy(proxy.to_internal()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_proxy(this: &PlayerHandle) -> *mut SailifyString {
+    this.with_player(|player| player.proxy().map(ToString::to_string))
+        .into_ffi()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_is_active(this: &PlayerHandle) -> bool {
+    this.with_player(|player| player.is_active())
+}
+
+/// Current position, interpolated while playing
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_current_position_ms(this: &PlayerHandle) -> u32 {
+    this.with_player(|player| player.current_position_ms())
+}
+
+/// Call the `position` callback every `interval_ms` while playing, 0 to disable it
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_position_interval_ms(
+    this: &PlayerHandle,
+    interval_ms: u32,
+) {
+    this.with_player(|player| {
+        player.set_position_interval(
+            Some(Duration::from_millis(u64::from(interval_ms))).filter(|_| interval_ms > 0),
+        )
+    });
 }
 
+/// Snapshot of the player state
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_get_password(
-    this: &mut SailifyPlayer,
-) -> SailifyStringView {
-    this.password().to_ffi()
+pub unsafe extern "C" fn sailify_player_get_state(this: &PlayerHandle) -> *mut SailifyState {
+    let snapshot = this.with_player(|player| player.state());
+    Box::into_raw(Box::new(SailifyState { snapshot }))
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_is_active(this: &mut SailifyPlayer) -> bool {
-    this.is_active()
+pub unsafe extern "C" fn sailify_player_refresh_access_token(this: &PlayerHandle) {
+    this.with_player(|player| player.refresh_access_token());
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_refresh_access_token(this: &mut SailifyPlayer) {
-    this.refresh_access_token();
+pub unsafe extern "C" fn sailify_player_network_changed(
+    this: &PlayerHandle,
+    online: bool,
+    metered: bool,
+) {
+    this.with_player(|player| player.network_changed(online, metered));
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_get_device_id(
-    this: &mut SailifyPlayer,
-) -> SailifyStringView {
-    this.device_id().to_ffi()
+pub unsafe extern "C" fn sailify_player_get_device_id(this: &PlayerHandle) -> *mut SailifyString {
+    this.with_player(|player| player.device_id().to_string())
+        .into_ffi()
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sailify_player_get_device_name(
-    this: &mut SailifyPlayer,
-) -> SailifyStringView {
-    this.device_name().to_ffi()
+pub unsafe extern "C" fn sailify_player_get_device_name(this: &PlayerHandle) -> *mut SailifyString {
+    this.with_player(|player| player.device_name().to_string())
+        .into_ffi()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_needs_restart(this: &PlayerHandle) -> bool {
+    this.with_player(|player| player.needs_restart())
+}
+
+// Settings
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub enum SailifyBitrate {
+    Bitrate96,
+    Bitrate160,
+    Bitrate320,
+}
+
+impl SailifyBitrate {
+    #[must_use]
+    pub fn from_raw(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(SailifyBitrate::Bitrate96),
+            1 => Some(SailifyBitrate::Bitrate160),
+            2 => Some(SailifyBitrate::Bitrate320),
+            _ => None,
+        }
+    }
+}
+
+impl From<SailifyBitrate> for Bitrate {
+    fn from(value: SailifyBitrate) -> Self {
+        match value {
+            SailifyBitrate::Bitrate96 => Bitrate::Bitrate96,
+            SailifyBitrate::Bitrate160 => Bitrate::Bitrate160,
+            SailifyBitrate::Bitrate320 => Bitrate::Bitrate320,
+        }
+    }
+}
+
+impl From<Bitrate> for SailifyBitrate {
+    fn from(value: Bitrate) -> Self {
+        match value {
+            Bitrate::Bitrate96 => SailifyBitrate::Bitrate96,
+            Bitrate::Bitrate160 => SailifyBitrate::Bitrate160,
+            Bitrate::Bitrate320 => SailifyBitrate::Bitrate320,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub enum SailifyAudioFormat {
+    F64,
+    F32,
+    S32,
+    S24,
+    S24_3,
+    S16,
+}
+
+impl SailifyAudioFormat {
+    #[must_use]
+    pub fn from_raw(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(SailifyAudioFormat::F64),
+            1 => Some(SailifyAudioFormat::F32),
+            2 => Some(SailifyAudioFormat::S32),
+            3 => Some(SailifyAudioFormat::S24),
+            4 => Some(SailifyAudioFormat::S24_3),
+            5 => Some(SailifyAudioFormat::S16),
+            _ => None,
+        }
+    }
+}
+
+impl From<SailifyAudioFormat> for AudioFormat {
+    fn from(value: SailifyAudioFormat) -> Self {
+        match value {
+            SailifyAudioFormat::F64 => AudioFormat::F64,
+            SailifyAudioFormat::F32 => AudioFormat::F32,
+            SailifyAudioFormat::S32 => AudioFormat::S32,
+            SailifyAudioFormat::S24 => AudioFormat::S24,
+            SailifyAudioFormat::S24_3 => AudioFormat::S24_3,
+            SailifyAudioFormat::S16 => AudioFormat::S16,
+        }
+    }
+}
+
+impl From<AudioFormat> for SailifyAudioFormat {
+    fn from(value: AudioFormat) -> Self {
+        match value {
+            AudioFormat::F64 => SailifyAudioFormat::F64,
+            AudioFormat::F32 => SailifyAudioFormat::F32,
+            AudioFormat::S32 => SailifyAudioFormat::S32,
+            AudioFormat::S24 => SailifyAudioFormat::S24,
+            AudioFormat::S24_3 => SailifyAudioFormat::S24_3,
+            AudioFormat::S16 => SailifyAudioFormat::S16,
+        }
+    }
+}
+
+/// Write optional value to `value` and return whether it exists
+fn optional_to_ffi<T: Copy>(optional: Option<T>, value: &mut T) -> bool {
+    if let Some(v) = optional {
+        *value = v;
+        true
+    } else {
+        false
+    }
+}
+
+fn optional_to_internal<T>(has_value: bool, value: T) -> Option<T> {
+    if has_value {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_bitrate(this: &PlayerHandle) -> SailifyBitrate {
+    this.with_player(|player| player.bitrate().into())
+}
+
+/// Set the bitrate to a `SailifyBitrate` value
+///
+/// Invalid values are reported as `IllegalConfig` error.
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_bitrate(this: &PlayerHandle, value: u32) -> bool {
+    this.with_player(|player| match SailifyBitrate::from_raw(value) {
+        Some(bitrate) => {
+            player.set_bitrate(bitrate.into());
+            true
+        }
+        None => {
+            player.set_error(LibrespotError::IllegalConfig(format!(
+                "Invalid bitrate {}",
+                value
+            )));
+            false
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_format(this: &PlayerHandle) -> SailifyAudioFormat {
+    this.with_player(|player| player.format().into())
+}
+
+/// Set the audio format to a `SailifyAudioFormat` value
+///
+/// Invalid values are reported as `IllegalConfig` error.
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_format(this: &PlayerHandle, value: u32) -> bool {
+    this.with_player(|player| match SailifyAudioFormat::from_raw(value) {
+        Some(format) => {
+            player.set_format(format.into());
+            true
+        }
+        None => {
+            player.set_error(LibrespotError::IllegalConfig(format!(
+                "Invalid audio format {}",
+                value
+            )));
+            false
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_backend(this: &PlayerHandle) -> *mut SailifyString {
+    this.with_player(|player| player.backend().map(ToString::to_string))
+        .into_ffi()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_backend(
+    this: &PlayerHandle,
+    value: SailifyStringView,
+) -> bool {
+    this.with_player(|player| player.set_backend(value.to_internal()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_gapless(this: &PlayerHandle) -> bool {
+    this.with_player(|player| player.gapless())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_gapless(this: &PlayerHandle, value: bool) {
+    this.with_player(|player| player.set_gapless(value));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_volume_normalisation(this: &PlayerHandle) -> bool {
+    this.with_player(|player| player.volume_normalisation())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_volume_normalisation(this: &PlayerHandle, value: bool) {
+    this.with_player(|player| player.set_volume_normalisation(value));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_normalisation_pregain(
+    this: &PlayerHandle,
+    value: &mut f64,
+) -> bool {
+    this.with_player(|player| optional_to_ffi(player.normalisation_pregain(), value))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_normalisation_pregain(
+    this: &PlayerHandle,
+    has_value: bool,
+    value: f64,
+) -> bool {
+    this.with_player(|player| {
+        player.set_normalisation_pregain(optional_to_internal(has_value, value))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_autoplay(this: &PlayerHandle) -> bool {
+    this.with_player(|player| player.autoplay())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_autoplay(this: &PlayerHandle, value: bool) {
+    this.with_player(|player| player.set_autoplay(value));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_discovery(this: &PlayerHandle) -> bool {
+    this.with_player(|player| player.discovery())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_discovery(this: &PlayerHandle, value: bool) {
+    this.with_player(|player| player.set_discovery(value));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_cache_size_limit(
+    this: &PlayerHandle,
+    value: &mut u64,
+) -> bool {
+    this.with_player(|player| optional_to_ffi(player.cache_size_limit(), value))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_cache_size_limit(
+    this: &PlayerHandle,
+    has_value: bool,
+    value: u64,
+) -> bool {
+    this.with_player(|player| player.set_cache_size_limit(optional_to_internal(has_value, value)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_initial_volume(
+    this: &PlayerHandle,
+    value: &mut u16,
+) -> bool {
+    this.with_player(|player| optional_to_ffi(player.initial_volume(), value))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_initial_volume(
+    this: &PlayerHandle,
+    has_value: bool,
+    value: u16,
+) -> bool {
+    this.with_player(|player| player.set_initial_volume(optional_to_internal(has_value, value)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_ap_port(this: &PlayerHandle, value: &mut u16) -> bool {
+    this.with_player(|player| optional_to_ffi(player.ap_port(), value))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_ap_port(
+    this: &PlayerHandle,
+    has_value: bool,
+    value: u16,
+) -> bool {
+    this.with_player(|player| player.set_ap_port(optional_to_internal(has_value, value)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_reconnect_max_attempts(
+    this: &PlayerHandle,
+    value: &mut u32,
+) -> bool {
+    this.with_player(|player| optional_to_ffi(player.reconnect_max_attempts(), value))
+}
+
+/// Pass `has_value = false` to never give up reconnecting
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_reconnect_max_attempts(
+    this: &PlayerHandle,
+    has_value: bool,
+    value: u32,
+) {
+    this.with_player(|player| {
+        player.set_reconnect_max_attempts(optional_to_internal(has_value, value))
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_reconnect_initial_delay_ms(this: &PlayerHandle) -> u64 {
+    this.with_player(|player| player.reconnect_initial_delay_ms())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_reconnect_initial_delay_ms(
+    this: &PlayerHandle,
+    value: u64,
+) -> bool {
+    this.with_player(|player| player.set_reconnect_initial_delay_ms(value))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_get_reconnect_max_delay_ms(this: &PlayerHandle) -> u64 {
+    this.with_player(|player| player.reconnect_max_delay_ms())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sailify_player_set_reconnect_max_delay_ms(
+    this: &PlayerHandle,
+    value: u64,
+) -> bool {
+    this.with_player(|player| player.set_reconnect_max_delay_ms(value))
 }
 
 // SailifyCallback
@@ -250,6 +882,24 @@ pub enum SailifyErrorKind {
     Connection,
     Panic,
     Token,
+    OAuth,
+    PremiumRequired,
+    BadCredentials,
+    NetworkUnreachable,
+    AccessPointRefused,
+    Timeout,
+    RateLimited,
+    AccountRestricted,
+}
+
+#[repr(C)]
+pub enum SailifyCommandStatus {
+    /// The command was handed to the Spirc, or for frames (seek, volume, shuffle, repeat, load)
+    /// accepted by the Spotify server. Spirc itself doesn't answer commands.
+    Completed,
+    NotConnected,
+    NotRunning,
+    Rejected,
 }
 
 #[repr(C)]
@@ -281,16 +931,53 @@ pub struct SailifyCallback {
     ),
     unavailable:
         unsafe fn(user_data: *mut c_void, play_request_id: u64, track_id: SailifyStringView),
+    /// Volume in percent (0-100)
     volume_changed: unsafe fn(user_data: *mut c_void, value: u16),
     connecting: unsafe fn(user_data: *mut c_void),
     connected: unsafe fn(user_data: *mut c_void),
     error: unsafe fn(user_data: *mut c_void, kind: SailifyErrorKind, message: SailifyStringView),
     shutdown: unsafe fn(user_data: *mut c_void),
     start_reconnect: unsafe fn(user_data: *mut c_void),
+    /// Started without a login, a Connect app can hand one over
+    waiting_for_discovery: unsafe fn(user_data: *mut c_void),
 
     token_changed:
         unsafe fn(user_data: *mut c_void, access_token: SailifyStringView, expires_in: u32),
 
+    shuffle_changed: unsafe fn(user_data: *mut c_void, shuffle: bool),
+    repeat_changed: unsafe fn(user_data: *mut c_void, repeat: RepeatMode),
+    /// Comma separated names of the settings applied live and of the settings that need a restart
+    settings_applied: unsafe fn(
+        user_data: *mut c_void,
+        applied: SailifyStringView,
+        needs_restart: SailifyStringView,
+    ),
+    reconnect_scheduled: unsafe fn(user_data: *mut c_void, attempt: u32, delay_ms: u64),
+    state_changed: unsafe fn(user_data: *mut c_void, state: &SailifyPlayerState),
+    /// Interpolated position, see `sailify_player_set_position_interval_ms`
+    position: unsafe fn(user_data: *mut c_void, position_ms: u32),
+    /// OAuth login succeeded, `sailify_player_start` logs in with the token
+    oauth_completed: unsafe fn(user_data: *mut c_void),
+    /// A Connect app handed over a login for `username`, connecting follows
+    remote_login: unsafe fn(user_data: *mut c_void, username: SailifyStringView),
+    /// Account of the session, `display_name` is null if unknown
+    account_info: unsafe fn(
+        user_data: *mut c_void,
+        username: SailifyStringView,
+        display_name: SailifyStringView,
+        country: SailifyStringView,
+        product: AccountProduct,
+    ),
+    login_progress: unsafe fn(user_data: *mut c_void, stage: LoginStage),
+    /// Result of the playback command with `request_id`, `message` is empty on success
+    command_result: unsafe fn(
+        user_data: *mut c_void,
+        request_id: u64,
+        command: SailifyStringView,
+        status: SailifyCommandStatus,
+        message: SailifyStringView,
+    ),
+
     destroy: unsafe fn(data: *mut c_void),
 }
 
@@ -364,12 +1051,73 @@ impl LibrespotEventListener for SailifyCallback {
                 } => {
                     (self.unavailable)(self.user_data, play_request_id, string_to_ffi(&track_id));
                 }
+                LibrespotEvent::Position { position_ms } => {
+                    (self.position)(self.user_data, position_ms);
+                }
                 LibrespotEvent::VolumeSet { volume } => {
-                    (self.volume_changed)(self.user_data, volume);
+                    (self.volume_changed)(self.user_data, raw_to_percent(volume));
+                }
+                LibrespotEvent::ShuffleChanged { shuffle } => {
+                    (self.shuffle_changed)(self.user_data, shuffle);
+                }
+                LibrespotEvent::RepeatChanged { repeat } => {
+                    (self.repeat_changed)(self.user_data, repeat);
+                }
+                LibrespotEvent::StateChanged { state } => {
+                    (self.state_changed)(self.user_data, &(&state).to_ffi());
+                }
+                LibrespotEvent::SettingsApplied { applied, restart } => {
+                    (self.settings_applied)(
+                        self.user_data,
+                        string_to_ffi(&setting_names(&applied)),
+                        string_to_ffi(&setting_names(&restart)),
+                    );
+                }
+                LibrespotEvent::OAuthCompleted => {
+                    (self.oauth_completed)(self.user_data);
+                }
+                LibrespotEvent::RemoteLogin { username } => {
+                    (self.remote_login)(self.user_data, string_to_ffi(&username));
+                }
+                LibrespotEvent::AccountInfo { info } => {
+                    (self.account_info)(
+                        self.user_data,
+                        string_to_ffi(&info.username),
+                        info.display_name.as_deref().to_ffi(),
+                        string_to_ffi(&info.country),
+                        info.product,
+                    );
                 }
                 LibrespotEvent::Connecting => {
                     (self.connecting)(self.user_data);
                 }
+                LibrespotEvent::LoginProgress { stage } => {
+                    (self.login_progress)(self.user_data, stage);
+                }
+                LibrespotEvent::CommandResult {
+                    request_id,
+                    command,
+                    result,
+                } => {
+                    let (status, message) = match &result {
+                        Ok(()) => (SailifyCommandStatus::Completed, String::new()),
+                        Err(err) => {
+                            let status = match err {
+                                CommandError::NotConnected(_) => SailifyCommandStatus::NotConnected,
+                                CommandError::NotRunning => SailifyCommandStatus::NotRunning,
+                                CommandError::Rejected(_) => SailifyCommandStatus::Rejected,
+                            };
+                            (status, err.to_string())
+                        }
+                    };
+                    (self.command_result)(
+                        self.user_data,
+                        request_id,
+                        command.to_ffi(),
+                        status,
+                        string_to_ffi(&message),
+                    );
+                }
                 LibrespotEvent::Connected => {
                     (self.connected)(self.user_data);
                 }
@@ -386,10 +1134,16 @@ impl LibrespotEventListener for SailifyCallback {
                 LibrespotEvent::StartReconnect => {
                     (self.start_reconnect)(self.user_data);
                 }
+                LibrespotEvent::WaitingForDiscovery => {
+                    (self.waiting_for_discovery)(self.user_data);
+                }
+                LibrespotEvent::ReconnectScheduled { attempt, delay_ms } => {
+                    (self.reconnect_scheduled)(self.user_data, attempt, delay_ms);
+                }
                 LibrespotEvent::TokenChanged { token: result } => match result {
                     Ok(token) => (self.token_changed)(
                         self.user_data,
-                        string_to_ffi(&token.access_token),
+                        string_to_ffi(token.access_token.expose()),
                         token.expires_in,
                     ),
                     Err(err) => {
@@ -402,6 +1156,18 @@ impl LibrespotEventListener for SailifyCallback {
                         LibrespotError::IllegalConfig(_) => SailifyErrorKind::IllegalConfig,
                         LibrespotError::Io(_) => SailifyErrorKind::Io,
                         LibrespotError::Connection(_) => SailifyErrorKind::Connection,
+                        LibrespotError::OAuth(_) => SailifyErrorKind::OAuth,
+                        LibrespotError::PremiumRequired => SailifyErrorKind::PremiumRequired,
+                        LibrespotError::BadCredentials => SailifyErrorKind::BadCredentials,
+                        LibrespotError::NetworkUnreachable(_) => {
+                            SailifyErrorKind::NetworkUnreachable
+                        }
+                        LibrespotError::AccessPointRefused(_) => {
+                            SailifyErrorKind::AccessPointRefused
+                        }
+                        LibrespotError::Timeout(_) => SailifyErrorKind::Timeout,
+                        LibrespotError::RateLimited(_) => SailifyErrorKind::RateLimited,
+                        LibrespotError::AccountRestricted(_) => SailifyErrorKind::AccountRestricted,
                         LibrespotError::Panic(_) => SailifyErrorKind::Panic,
                     };
                     let error_string = format!("{}", &err);
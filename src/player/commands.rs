@@ -0,0 +1,216 @@
+//! Playback commands
+//!
+//! Every command gets a request ID, and its result is reported with a
+//! [`LibrespotEvent::CommandResult`](crate::player::events::LibrespotEvent) carrying that ID.
+//!
+//! Commands that arrive while there is no connection are kept until the session is connected
+//! again, but only for a limited time: a "play" from minutes ago would surprise the user.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use quick_error::quick_error;
+
+use crate::player::reconnect::Clock;
+use crate::player::RepeatMode;
+
+/// Time a command waits for a connection
+pub const COMMAND_LIFETIME: Duration = Duration::from_secs(30);
+/// Commands kept at most, older ones are dropped first
+pub const MAX_QUEUED_COMMANDS: usize = 16;
+
+/// Identifies a command in its result, never 0
+pub type RequestId = u64;
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    Play,
+    Pause,
+    Next,
+    Previous,
+    Seek {
+        position_ms: u32,
+    },
+    SetVolume {
+        volume: u16,
+    },
+    VolumeUp,
+    VolumeDown,
+    SetShuffle {
+        shuffle: bool,
+    },
+    SetRepeat {
+        repeat: RepeatMode,
+    },
+    Load {
+        context_uri: String,
+        track_uris: Vec<String>,
+        start_index: u32,
+        position_ms: u32,
+        shuffle: bool,
+    },
+}
+
+impl Command {
+    /// Name of the command for logs and results
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Play => "play",
+            Command::Pause => "pause",
+            Command::Next => "next",
+            Command::Previous => "previous",
+            Command::Seek { .. } => "seek",
+            Command::SetVolume { .. } => "set-volume",
+            Command::VolumeUp => "volume-up",
+            Command::VolumeDown => "volume-down",
+            Command::SetShuffle { .. } => "set-shuffle",
+            Command::SetRepeat { .. } => "set-repeat",
+            Command::Load { .. } => "load",
+        }
+    }
+}
+
+quick_error! {
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum CommandError {
+        /// Dropped while waiting for a connection
+        NotConnected(reason: String) {
+            display("Not connected: {}", reason)
+        }
+
+        /// The runtime is stopped or crashed
+        NotRunning {
+            display("The player is not running")
+        }
+
+        /// Spirc did not accept or never received the command
+        Rejected(reason: String) {
+            display("Command failed: {}", reason)
+        }
+    }
+}
+
+pub type CommandResult = Result<(), CommandError>;
+
+/// A command waiting for a connection
+pub struct QueuedCommand {
+    pub request_id: RequestId,
+    pub command: Command,
+    deadline: Instant,
+}
+
+pub struct CommandQueue {
+    commands: VecDeque<QueuedCommand>,
+    clock: Arc<dyn Clock>,
+}
+
+impl CommandQueue {
+    #[must_use]
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            commands: VecDeque::new(),
+            clock,
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Queue a command, returning the oldest command if the queue is full
+    pub fn push(&mut self, request_id: RequestId, command: Command) -> Option<QueuedCommand> {
+        let dropped = if self.commands.len() >= MAX_QUEUED_COMMANDS {
+            self.commands.pop_front()
+        } else {
+            None
+        };
+        self.commands.push_back(QueuedCommand {
+            request_id,
+            command,
+            deadline: self.clock.now() + COMMAND_LIFETIME,
+        });
+        dropped
+    }
+
+    /// Remove the commands that waited too long
+    pub fn take_expired(&mut self) -> Vec<QueuedCommand> {
+        let now = self.clock.now();
+        let mut expired = Vec::new();
+        while let Some(queued) = self.commands.front() {
+            if queued.deadline > now {
+                break;
+            }
+            if let Some(queued) = self.commands.pop_front() {
+                expired.push(queued);
+            }
+        }
+        expired
+    }
+
+    /// Remove all commands, oldest first
+    pub fn take_all(&mut self) -> Vec<QueuedCommand> {
+        self.commands.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::reconnect::ManualClock;
+
+    fn new_queue() -> (CommandQueue, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new());
+        (CommandQueue::new(clock.clone()), clock)
+    }
+
+    fn request_ids(commands: &[QueuedCommand]) -> Vec<RequestId> {
+        commands.iter().map(|queued| queued.request_id).collect()
+    }
+
+    #[test]
+    fn drops_the_oldest_command_when_full() {
+        let (mut queue, _) = new_queue();
+        for request_id in 1..=MAX_QUEUED_COMMANDS as RequestId {
+            assert!(queue.push(request_id, Command::Play).is_none());
+        }
+
+        let dropped = queue.push(17, Command::Pause).unwrap();
+        assert_eq!(dropped.request_id, 1);
+        let remaining = queue.take_all();
+        assert_eq!(remaining.len(), MAX_QUEUED_COMMANDS);
+        assert_eq!(remaining[0].request_id, 2);
+        assert_eq!(remaining[MAX_QUEUED_COMMANDS - 1].request_id, 17);
+    }
+
+    #[test]
+    fn expires_commands_after_their_lifetime() {
+        let (mut queue, clock) = new_queue();
+        queue.push(1, Command::Play);
+        clock.advance(Duration::from_secs(10));
+        queue.push(2, Command::Next);
+
+        clock.advance(COMMAND_LIFETIME - Duration::from_secs(11));
+        assert!(queue.take_expired().is_empty());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(request_ids(&queue.take_expired()), vec![1]);
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(request_ids(&queue.take_expired()), vec![2]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn drains_oldest_first() {
+        let (mut queue, _) = new_queue();
+        queue.push(3, Command::Play);
+        queue.push(1, Command::Seek { position_ms: 1000 });
+        queue.push(2, Command::Pause);
+
+        let names: Vec<_> = queue.take_all().iter().map(|q| q.command.name()).collect();
+        assert_eq!(names, vec!["play", "seek", "pause"]);
+        assert!(queue.is_empty());
+        assert!(queue.take_all().is_empty());
+    }
+}
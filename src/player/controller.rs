@@ -1,74 +1,184 @@
-use std::sync::Arc;
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures::StreamExt;
 use librespot_connect::spirc::Spirc;
-use librespot_core::authentication::Credentials;
 use librespot_core::cache::Cache;
 use librespot_core::config::{ConnectConfig, SessionConfig};
-use librespot_core::keymaster::get_token;
 use librespot_core::session::Session;
+use librespot_discovery::Discovery;
 use librespot_playback::audio_backend::SinkBuilder;
-use librespot_playback::config::{AudioFormat, PlayerConfig};
-use librespot_playback::mixer::{MixerConfig, MixerFn};
+use librespot_playback::config::{AudioFormat, Bitrate, PlayerConfig};
+use librespot_playback::mixer::{Mixer, MixerConfig, MixerFn};
 use librespot_playback::player::{Player, PlayerEventChannel};
 use log::{error, info, warn};
 use tokio::runtime::Handle;
-
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use url::Url;
+
+use crate::player::account::{fetch_account_info, AccountInfo, AccountProduct};
+use crate::player::commands::{
+    Command, CommandError, CommandQueue, CommandResult, QueuedCommand, RequestId, COMMAND_LIFETIME,
+};
+use crate::player::credentials::CredentialManager;
+use crate::player::error::LibrespotError;
 use crate::player::events::{LibrespotEvent, LibrespotEventListener, LibrespotEventListenerRef};
-use crate::player::{CLIENT_ID, SCOPES};
+use crate::player::login::{classify_io_error, classify_session_error, is_retryable, LoginStage};
+use crate::player::mixer::LiveMixer;
+use crate::player::options::SettingsChanges;
+use crate::player::proxy::{start_bridge, AccessPointWatch, ProxyConfig};
+use crate::player::reconnect::{Backoff, Clock, ReconnectAttempt, ReconnectPolicy};
+use crate::player::remote::{
+    await_response, watch_state, SharedSpircState, SpircHellos, SpircRemote,
+};
+use crate::player::secret::{SecretCredentials, SecretString};
+use crate::player::state::{run_position_ticker, SharedState};
+use crate::player::token::{fetch_token, run_token_refresher};
+use crate::player::RepeatMode;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone)]
 pub enum ControlMessage {
     Shutdown,
-    Play,
-    Pause,
-    Next,
-    Previous,
+    /// Remove the stored credentials and shut down, cancelling a login in progress
+    Logout,
+    /// A playback command, its result is reported with `request_id`
+    Command {
+        request_id: RequestId,
+        command: Command,
+    },
 
     RefreshToken,
+    /// Report the position periodically, `None` to stop
+    SetPositionInterval {
+        interval: Option<Duration>,
+    },
+    /// The network connectivity of the device changed
+    NetworkChanged {
+        online: bool,
+        metered: bool,
+    },
+    Reconfigure {
+        playback: Box<PlaybackConfig>,
+        changes: SettingsChanges,
+        reconnect_policy: ReconnectPolicy,
+    },
 
     // internal
-    AutoReconnect,
+    AutoReconnect {
+        generation: u64,
+    },
+    /// A scheduled reconnect is due
+    Reconnect {
+        generation: u64,
+    },
+    /// A Connect app handed over a login
+    Discovered {
+        credentials: SecretCredentials,
+    },
+    /// The account of the session can not stream
+    PremiumRequired {
+        session_id: usize,
+    },
+    /// Queued commands may have waited too long
+    ExpireCommands,
 }
 
+/// Time the access point has to accept a login
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Everything needed to build a player and a Spirc for a session
 #[derive(Clone)]
-pub struct LibrespotConfig {
+pub struct PlaybackConfig {
     pub format: AudioFormat,
     pub backend: SinkBuilder,
     pub device: Option<String>,
 
     pub mixer: MixerFn,
 
-    pub cache: Cache,
     pub player_config: PlayerConfig,
-    pub session_config: SessionConfig,
     pub connect_config: ConnectConfig,
     pub mixer_config: MixerConfig,
-    pub credentials: Credentials,
+}
+
+#[derive(Clone)]
+pub struct LibrespotConfig {
+    pub playback: PlaybackConfig,
+    pub cache: Cache,
+    pub proxy: Option<ProxyConfig>,
+    pub session_config: SessionConfig,
+    /// `None` to wait for a login from a Connect app
+    pub credentials: Option<SecretCredentials>,
+    /// Advertise the device, so that Connect apps can hand over a login
+    pub discovery: bool,
+    /// Moves the credentials librespot caches into the credential store
+    pub credential_manager: CredentialManager,
+    pub reconnect_policy: ReconnectPolicy,
+    pub clock: Arc<dyn Clock>,
+}
+
+/// Why a login did not connect
+enum LoginFailed {
+    /// The error was already reported
+    Error {
+        /// Connecting again may succeed
+        retryable: bool,
+    },
+    /// The account can not stream and was logged out, the runtime keeps running
+    Rejected,
+    /// A shutdown or logout was requested meanwhile and is done
+    Cancelled,
+    /// The network changed meanwhile, the login has to start over
+    NetworkChanged,
+}
+
+impl LoginFailed {
+    fn retryable(&self) -> bool {
+        matches!(self, LoginFailed::Error { retryable: true })
+    }
 }
 
 pub struct LibrespotController {
     cache: Cache,
-    player_config: PlayerConfig,
+    proxy: Option<ProxyConfig>,
+    /// Reports the progress of `Session::connect` through the proxy bridge
+    access_points: Arc<AccessPointWatch>,
     session_config: SessionConfig,
-    connect_config: ConnectConfig,
-    format: AudioFormat,
-    backend: SinkBuilder,
-    device: Option<String>,
-    mixer: MixerFn,
-    mixer_config: MixerConfig,
+    playback: PlaybackConfig,
     handle: Handle,
 
     control_rx: UnboundedReceiver<ControlMessage>,
     control_tx: UnboundedSender<ControlMessage>,
+    /// Messages that arrived during a login, handled after it
+    deferred: VecDeque<ControlMessage>,
+    /// Playback commands waiting for a connection
+    pending_commands: CommandQueue,
 
     spirc: Option<Spirc>,
+    /// Mixer of the Spirc, replaced for changed mixer settings
+    mixer: Option<LiveMixer>,
+    remote: Option<SpircRemote>,
     session: Option<Session>,
-
-    credentials: Credentials,
-    auto_connect_times: Vec<Instant>,
+    spirc_state: SharedSpircState,
+    spirc_hellos: SpircHellos,
+    /// Incremented for every Spirc, so that replaced Spircs don't trigger a reconnect
+    spirc_generation: u64,
+    token_requests: Option<UnboundedSender<()>>,
+    discovery: Option<JoinHandle<()>>,
+
+    credentials: Option<SecretCredentials>,
+    credential_manager: CredentialManager,
+    backoff: Backoff,
+    online: bool,
+    /// Stream at a low bitrate to save data
+    metered: bool,
+    state: SharedState,
+    position_ticker: Option<JoinHandle<()>>,
+    /// The Shutdown event was sent
+    stopped: bool,
 
     listener: Arc<dyn LibrespotEventListener>,
 }
@@ -79,137 +189,626 @@ impl LibrespotController {
         control_tx: UnboundedSender<ControlMessage>,
         control_rx: UnboundedReceiver<ControlMessage>,
         listener: Arc<dyn LibrespotEventListener>,
+        state: SharedState,
         setup: LibrespotConfig,
     ) {
         let self_ = LibrespotController {
             handle: handle.clone(),
             cache: setup.cache,
+            proxy: setup.proxy,
+            access_points: Arc::new(AccessPointWatch::new(listener.clone())),
             session_config: setup.session_config,
-            player_config: setup.player_config,
-            connect_config: setup.connect_config,
-            format: setup.format,
-            backend: setup.backend,
-            device: setup.device,
-            mixer: setup.mixer,
-            mixer_config: setup.mixer_config,
+            playback: setup.playback,
 
             spirc: None,
+            mixer: None,
+            remote: None,
             session: None,
+            spirc_state: Arc::new(Mutex::new(None)),
+            spirc_hellos: Arc::new(watch::channel(0).0),
+            spirc_generation: 0,
+            token_requests: None,
+            discovery: None,
 
             credentials: setup.credentials,
-            auto_connect_times: Vec::new(),
+            credential_manager: setup.credential_manager,
+            pending_commands: CommandQueue::new(setup.clock.clone()),
+            backoff: Backoff::new(setup.reconnect_policy, setup.clock),
+            online: true,
+            metered: false,
+            state,
+            position_ticker: None,
+            stopped: false,
             control_rx,
             control_tx,
+            deferred: VecDeque::new(),
 
             listener,
         };
-        self_.run_internal().await;
+        self_.run_internal(setup.discovery).await;
     }
 
-    async fn get_token(session: Session, listener: LibrespotEventListenerRef) {
-        let token_result = get_token(&session, CLIENT_ID, SCOPES).await;
-        listener.notify(LibrespotEvent::TokenChanged {
-            token: token_result.map_err(|err| format!("{:?}", err)),
-        });
+    pub async fn run_internal(mut self, discovery: bool) {
+        self.run_until_stopped(discovery).await;
+        if !self.stopped {
+            // gave up, the host still has to learn that the runtime ended
+            self.shutdown();
+        }
+        for queued in self.pending_commands.take_all() {
+            self.reject_command(queued, "The player stopped");
+        }
     }
 
-    pub async fn run_internal(mut self) {
-        if !self.login().await {
+    async fn run_until_stopped(&mut self, discovery: bool) {
+        if !self.start_proxy_bridge().await {
+            return;
+        }
+        if discovery && !self.start_discovery() {
             return;
         }
+        if self.credentials.is_some() {
+            if !self.connect().await {
+                return;
+            }
+        } else {
+            info!("Waiting for a Connect app to hand over a login ...");
+            self.listener.notify(LibrespotEvent::WaitingForDiscovery);
+        }
 
-        while let Some(msg) = self.control_rx.next().await {
-            if let Some(ref spirc) = self.spirc {
-                match msg {
-                    ControlMessage::Play => spirc.play(),
-                    ControlMessage::Next => spirc.next(),
-                    ControlMessage::Pause => spirc.pause(),
-                    ControlMessage::Previous => spirc.prev(),
-                    ControlMessage::Shutdown => {
-                        self.shutdown();
-                        return;
-                    }
-                    ControlMessage::AutoReconnect => {
-                        if !self.autoreconnect().await {
+        loop {
+            if self.spirc.is_some() && !self.pending_commands.is_empty() {
+                for queued in self.pending_commands.take_all() {
+                    info!("Connected, running queued {}", queued.command.name());
+                    self.handle_command(queued.request_id, queued.command);
+                }
+            }
+
+            let msg = match self.deferred.pop_front() {
+                Some(msg) => msg,
+                None => match self.control_rx.next().await {
+                    Some(msg) => msg,
+                    None => return,
+                },
+            };
+            match msg {
+                ControlMessage::Shutdown => {
+                    self.shutdown();
+                    return;
+                }
+                ControlMessage::Logout => {
+                    self.logout();
+                    return;
+                }
+                ControlMessage::AutoReconnect { generation } => {
+                    if generation == self.spirc_generation {
+                        warn!("Spirc shut down unexpectedly");
+                        self.listener.notify(LibrespotEvent::StartReconnect);
+                        if !self.schedule_reconnect() {
                             return;
                         }
                     }
-                    ControlMessage::RefreshToken => {
-                        if let Some(session) = &self.session {
-                            self.handle
-                                .spawn(Self::get_token(session.clone(), self.listener.clone()));
+                }
+                ControlMessage::Reconnect { generation } => {
+                    if generation == self.spirc_generation && !self.reconnect().await {
+                        return;
+                    }
+                }
+                ControlMessage::Discovered { credentials } => {
+                    info!("Login handed over for {:?}", credentials.username());
+                    self.listener.notify(LibrespotEvent::RemoteLogin {
+                        username: credentials.username().to_string(),
+                    });
+                    self.credentials = Some(credentials);
+                    self.disconnect();
+                    self.backoff.reset();
+                    if !self.reconnect().await {
+                        return;
+                    }
+                }
+                ControlMessage::PremiumRequired { session_id } => {
+                    if self.session.as_ref().map(Session::session_id) == Some(session_id) {
+                        warn!("Account can not stream, Premium is required");
+                        self.reject_account();
+                    }
+                }
+                ControlMessage::ExpireCommands => {
+                    for queued in self.pending_commands.take_expired() {
+                        self.reject_command(queued, "Not connected in time");
+                    }
+                }
+                ControlMessage::SetPositionInterval { interval } => {
+                    if let Some(position_ticker) = self.position_ticker.take() {
+                        position_ticker.abort();
+                    }
+                    if let Some(interval) = interval {
+                        self.position_ticker = Some(self.handle.spawn(run_position_ticker(
+                            self.state.clone(),
+                            self.listener.clone(),
+                            interval,
+                        )));
+                    }
+                }
+                ControlMessage::NetworkChanged { online, metered } => {
+                    if !self.network_changed(online, metered).await {
+                        return;
+                    }
+                }
+                ControlMessage::RefreshToken => {
+                    if let Some(token_requests) = &self.token_requests {
+                        let _ = token_requests.unbounded_send(());
+                    }
+                }
+                ControlMessage::Reconfigure {
+                    playback,
+                    changes,
+                    reconnect_policy,
+                } => {
+                    self.playback = *playback;
+                    self.backoff.set_policy(reconnect_policy);
+                    if changes.needs_rebuild() {
+                        self.rebuild_spirc();
+                    } else if changes.needs_new_mixer() {
+                        if let Some(mixer) = &self.mixer {
+                            mixer.replace(self.playback.mixer, self.playback.mixer_config.clone());
                         }
                     }
-                };
+                    let applied = changes.applied();
+                    info!(
+                        "Applied settings {:?}, restart needed for {:?}",
+                        applied, changes.restart
+                    );
+                    self.listener.notify(LibrespotEvent::SettingsApplied {
+                        applied,
+                        restart: changes.restart,
+                    });
+                }
+                ControlMessage::Command {
+                    request_id,
+                    command,
+                } => self.handle_command(request_id, command),
+            }
+        }
+    }
+
+    /// Forward a playback command to Spirc, queueing it while not connected
+    fn handle_command(&mut self, request_id: RequestId, command: Command) {
+        let (spirc, remote) = match (&self.spirc, &mut self.remote) {
+            (Some(spirc), Some(remote)) => (spirc, remote),
+            _ => return self.queue_command(request_id, command),
+        };
+
+        let name = command.name();
+        match command {
+            Command::Play => spirc.play(),
+            Command::Next => spirc.next(),
+            Command::Pause => spirc.pause(),
+            Command::Previous => spirc.prev(),
+            Command::VolumeUp => spirc.volume_up(),
+            Command::VolumeDown => spirc.volume_down(),
+            Command::Seek { position_ms } => {
+                let response = await_response(remote.seek(position_ms));
+                return self.spawn_command(request_id, name, response);
+            }
+            Command::SetVolume { volume } => {
+                let response = await_response(remote.set_volume(volume));
+                return self.spawn_command(request_id, name, response);
+            }
+            Command::SetShuffle { shuffle } => {
+                let response = await_response(remote.set_shuffle(shuffle));
+                return self.spawn_command(request_id, name, response);
+            }
+            Command::SetRepeat { repeat } => {
+                let response = await_response(remote.set_repeat(repeat == RepeatMode::Context));
+                return self.spawn_command(request_id, name, response);
+            }
+            Command::Load {
+                context_uri,
+                track_uris,
+                start_index,
+                position_ms,
+                shuffle,
+            } => {
+                let response =
+                    remote.load(context_uri, track_uris, start_index, position_ms, shuffle);
+                return self.spawn_command(request_id, name, response);
+            }
+        }
+        // Spirc takes these commands without answering
+        self.notify_result(request_id, name, Ok(()));
+    }
+
+    /// Report the result of a command once `response` is done
+    fn spawn_command(
+        &self,
+        request_id: RequestId,
+        command: &'static str,
+        response: impl Future<Output = CommandResult> + Send + 'static,
+    ) {
+        let mut pending = PendingResult {
+            request_id,
+            command,
+            listener: Some(self.listener.clone()),
+        };
+        self.handle.spawn(async move {
+            let result = response.await;
+            pending.finish(result);
+        });
+    }
+
+    fn notify_result(&self, request_id: RequestId, command: &'static str, result: CommandResult) {
+        if let Err(err) = &result {
+            warn!("Command {} ({}) failed: {}", command, request_id, err);
+        }
+        self.listener.notify(LibrespotEvent::CommandResult {
+            request_id,
+            command,
+            result,
+        });
+    }
+
+    fn queue_command(&mut self, request_id: RequestId, command: Command) {
+        info!(
+            "Not connected, queueing {} ({})",
+            command.name(),
+            request_id
+        );
+        if let Some(dropped) = self.pending_commands.push(request_id, command) {
+            self.reject_command(dropped, "Too many commands while not connected");
+        }
+
+        let control_tx = self.control_tx.clone();
+        self.handle.spawn(async move {
+            tokio::time::sleep(COMMAND_LIFETIME).await;
+            let _ = control_tx.unbounded_send(ControlMessage::ExpireCommands);
+        });
+    }
+
+    fn reject_command(&self, queued: QueuedCommand, reason: &str) {
+        self.notify_result(
+            queued.request_id,
+            queued.command.name(),
+            Err(CommandError::NotConnected(reason.to_string())),
+        );
+    }
+
+    /// Wait for `future` while watching the control messages
+    ///
+    /// A shutdown, a logout or a network change interrupts the login, other messages are deferred
+    /// until it is done.
+    async fn interruptible<F: Future>(&mut self, future: F) -> Result<F::Output, LoginFailed> {
+        tokio::pin!(future);
+        loop {
+            let msg = tokio::select! {
+                output = &mut future => return Ok(output),
+                msg = self.control_rx.next() => msg,
+            };
+            match msg {
+                Some(ControlMessage::Shutdown) | None => {
+                    info!("Shutdown while logging in");
+                    self.shutdown();
+                    return Err(LoginFailed::Cancelled);
+                }
+                Some(ControlMessage::Logout) => {
+                    info!("Logout while logging in");
+                    self.logout();
+                    return Err(LoginFailed::Cancelled);
+                }
+                Some(ControlMessage::NetworkChanged { online, metered }) => {
+                    info!("Network changed while logging in");
+                    self.set_network(online, metered);
+                    return Err(LoginFailed::NetworkChanged);
+                }
+                Some(msg) => self.deferred.push_back(msg),
             }
         }
     }
 
-    async fn login(&mut self) -> bool {
+    async fn start_proxy_bridge(&mut self) -> bool {
+        match start_bridge(self.proxy.clone(), self.access_points.clone()).await {
+            Ok(url) => {
+                self.session_config.proxy = Some(url);
+                true
+            }
+            Err(err) => {
+                error!("Could not start proxy bridge: {:?}", err);
+                self.listener.notify(LibrespotEvent::ConnectionError {
+                    message: format!("Could not start proxy bridge: {}", err),
+                });
+                false
+            }
+        }
+    }
+
+    /// Advertise the device on the local network and forward logins of Connect apps
+    fn start_discovery(&mut self) -> bool {
+        let discovery = Discovery::builder(self.session_config.device_id.clone())
+            .name(self.playback.connect_config.name.clone())
+            .device_type(self.playback.connect_config.device_type)
+            .launch();
+        let mut discovery = match discovery {
+            Ok(discovery) => discovery,
+            Err(err) => {
+                error!("Could not start discovery: {}", err);
+                self.listener.notify(LibrespotEvent::Error {
+                    err: LibrespotError::Connection(format!("Could not start discovery: {}", err)),
+                });
+                return false;
+            }
+        };
+
+        info!("Started discovery");
+        let control_tx = self.control_tx.clone();
+        self.discovery = Some(self.handle.spawn(async move {
+            while let Some(credentials) = discovery.next().await {
+                let _ = control_tx.unbounded_send(ControlMessage::Discovered {
+                    credentials: credentials.into(),
+                });
+            }
+        }));
+        true
+    }
+
+    /// Connect a new session
+    ///
+    /// Failures are reported to the listener, except missing credentials.
+    async fn login(&mut self) -> Result<(), LoginFailed> {
+        let credentials = match &self.credentials {
+            // Session::connect takes them by value, librespot drops that copy without wiping it
+            Some(credentials) => credentials.expose().clone(),
+            None => return Err(LoginFailed::Error { retryable: false }),
+        };
+
         info!("Logging in ...");
         self.spirc = None;
+        self.remote = None;
+        self.token_requests = None;
         self.listener.notify(LibrespotEvent::Connecting);
 
-        // connect with credentials
+        // connect with credentials, the proxy bridge reports the following stages
+        self.notify_stage(LoginStage::ResolvingAccessPoint);
+        self.access_points.take_error();
         let session_future = Session::connect(
             self.session_config.clone(),
-            self.credentials.clone(),
+            credentials,
             Some(self.cache.clone()),
         );
-        let session = match session_future.await {
+        let result = match self
+            .interruptible(tokio::time::timeout(CONNECT_TIMEOUT, session_future))
+            .await?
+        {
+            Ok(Ok(session)) => Ok(session),
+            Ok(Err(err)) => {
+                error!("Could not connect to server: {:?}", err);
+                // librespot only saw the bridge refuse the tunnel
+                Err(match self.access_points.take_error() {
+                    Some(err) => classify_io_error(&err),
+                    None => classify_session_error(&err),
+                })
+            }
+            Err(_) => {
+                error!("Connecting to server timed out");
+                Err(LibrespotError::Timeout(
+                    "No answer from the access point".to_string(),
+                ))
+            }
+        };
+        let session = match result {
             Ok(session) => session,
-            Err(error) => {
-                error!("Could not connect to server: {:?}", error);
-                self.listener.notify(LibrespotEvent::ConnectionError {
-                    message: format!("{:?}", error),
-                });
-                return false;
+            Err(LibrespotError::PremiumRequired) => {
+                warn!("Account can not stream, Premium is required");
+                self.reject_account();
+                return Err(LoginFailed::Rejected);
+            }
+            Err(err) => {
+                let retryable = is_retryable(&err);
+                self.listener.notify(LibrespotEvent::Error { err });
+                return Err(LoginFailed::Error { retryable });
             }
         };
         self.session = Some(session.clone());
         info!("Connected");
+        self.use_stored_credentials();
+
+        self.notify_stage(LoginStage::StartingSpirc);
+        *self.spirc_state.lock().unwrap() = None;
+        // watch before starting the Spirc, so that its hello is seen
+        self.handle.spawn(watch_state(
+            session.clone(),
+            self.listener.clone(),
+            self.spirc_state.clone(),
+            self.spirc_hellos.clone(),
+        ));
+        self.start_spirc(&session, self.playback.connect_config.clone());
+        self.remote = Some(SpircRemote::new(session.clone()));
+
+        // get token and keep it fresh, replacing the refresher of a previous session
+        self.notify_stage(LoginStage::FetchingToken);
+        let token = self.interruptible(fetch_token(&session)).await?;
+        if let Ok(token) = &token {
+            self.handle.spawn(Self::check_account(
+                session.clone(),
+                token.access_token.clone(),
+                self.session_config.proxy.clone(),
+                self.listener.clone(),
+                self.control_tx.clone(),
+            ));
+        }
+        self.listener.notify(LibrespotEvent::TokenChanged {
+            token: token.clone(),
+        });
+        let (token_tx, token_rx) = unbounded();
+        self.token_requests = Some(token_tx);
+        self.handle.spawn(run_token_refresher(
+            session.clone(),
+            self.listener.clone(),
+            token_rx,
+            token,
+        ));
+        self.backoff.connected();
+        self.listener.notify(LibrespotEvent::Connected);
+
+        Ok(())
+    }
+
+    fn notify_stage(&self, stage: LoginStage) {
+        info!("Login stage: {:?}", stage);
+        self.listener
+            .notify(LibrespotEvent::LoginProgress { stage });
+    }
+
+    /// Report the account of `session` and whether it can stream
+    async fn check_account(
+        session: Session,
+        access_token: SecretString,
+        proxy: Option<Url>,
+        listener: LibrespotEventListenerRef,
+        control_tx: UnboundedSender<ControlMessage>,
+    ) {
+        let country = session.country();
+        let result = tokio::task::spawn_blocking(move || {
+            fetch_account_info(access_token.expose(), proxy.as_ref(), country)
+        })
+        .await;
+        let info = match result {
+            Ok(Ok(info)) => info,
+            Ok(Err(err)) => {
+                warn!("Could not read account information: {}", err);
+                AccountInfo {
+                    username: session.username(),
+                    display_name: None,
+                    country: session.country(),
+                    product: AccountProduct::Unknown,
+                }
+            }
+            Err(err) => {
+                error!("Account information request crashed: {}", err);
+                return;
+            }
+        };
+
+        info!(
+            "Account {:?} ({:?}, {})",
+            info.username, info.product, info.country
+        );
+        let can_stream = info.can_stream();
+        listener.notify(LibrespotEvent::AccountInfo { info });
+        if !can_stream {
+            let _ = control_tx.unbounded_send(ControlMessage::PremiumRequired {
+                session_id: session.session_id(),
+            });
+        }
+    }
 
-        // setup
-        let mixer_config = self.mixer_config.clone();
-        let mixer = (self.mixer)(mixer_config);
-        let player_config = self.player_config.clone();
-        let connect_config = self.connect_config.clone();
+    /// Log out of an account that can not stream
+    ///
+    /// The access point refuses the login of such an account, or the account information tells
+    /// after it. Its stored credentials are removed, so that they are not used again. The runtime
+    /// keeps running, a Connect app can still hand over the login of another account.
+    fn reject_account(&mut self) {
+        self.disconnect();
+        self.credentials = None;
+        if let Err(err) = self.credential_manager.remove() {
+            error!("Could not remove credentials: {}", err);
+        }
+        for queued in self.pending_commands.take_all() {
+            self.reject_command(queued, "The account can not stream");
+        }
+        self.listener.notify(LibrespotEvent::Error {
+            err: LibrespotError::PremiumRequired,
+        });
+    }
+
+    /// Forget the credentials and shut down
+    ///
+    /// A login that was interrupted doesn't get to store its reusable credentials.
+    fn logout(&mut self) {
+        self.credentials = None;
+        if let Err(err) = self.credential_manager.remove() {
+            error!("Could not remove credentials: {}", err);
+        }
+        self.shutdown();
+    }
+
+    /// Store the reusable credentials and use them instead of a password or token from now on
+    fn use_stored_credentials(&mut self) {
+        match self.credential_manager.load() {
+            Ok(Some(stored)) => self.credentials = Some(stored.into()),
+            Ok(None) => warn!("No reusable credentials were stored"),
+            Err(err) => error!("Could not store credentials: {}", err),
+        }
+    }
+
+    fn start_spirc(&mut self, session: &Session, connect_config: ConnectConfig) {
+        let playback = &self.playback;
+        let mixer = LiveMixer::new(playback.mixer, playback.mixer_config.clone());
 
         let audio_filter = mixer.get_audio_filter();
-        let format = self.format;
-        let backend = self.backend;
-        let device = self.device.clone();
+        let format = playback.format;
+        let backend = playback.backend;
+        let device = playback.device.clone();
+        let mut player_config = playback.player_config.clone();
+        if self.metered && player_config.bitrate != Bitrate::Bitrate96 {
+            info!("Metered network, streaming at 96 kbit/s");
+            player_config.bitrate = Bitrate::Bitrate96;
+        }
         let (player, event_channel) =
             Player::new(player_config, session.clone(), audio_filter, move || {
                 (backend)(device, format)
             });
 
-        let (spirc, spirc_task) = Spirc::new(connect_config, session.clone(), player, mixer);
+        let (spirc, spirc_task) = Spirc::new(
+            connect_config,
+            session.clone(),
+            player,
+            Box::new(mixer.clone()),
+        );
         self.spirc = Some(spirc);
+        self.mixer = Some(mixer);
+        self.spirc_generation += 1;
 
+        let generation = self.spirc_generation;
         let control_tx = self.control_tx.clone();
         self.handle.spawn(async move {
             spirc_task.await;
-            let _ = control_tx.unbounded_send(ControlMessage::AutoReconnect);
+            let _ = control_tx.unbounded_send(ControlMessage::AutoReconnect { generation });
         });
 
         self.handle.spawn(Self::run_event_channel(
             event_channel,
             self.listener.clone(),
         ));
+    }
 
-        // get token
-        let token = get_token(&session, CLIENT_ID, SCOPES)
-            .await
-            .map_err(|err| format!("{:?}", err));
-        self.listener.notify(LibrespotEvent::TokenChanged { token });
-        self.listener.notify(LibrespotEvent::Connected);
+    /// Replace player and Spirc on the running session and continue playback where it was
+    fn rebuild_spirc(&mut self) {
+        let session = match &self.session {
+            Some(session) => session.clone(),
+            None => return,
+        };
 
-        true
+        info!("Rebuilding player ...");
+        let state = self.spirc_state.lock().unwrap().take();
+        if let Some(spirc) = self.spirc.take() {
+            spirc.shutdown();
+        }
+
+        // keep the current volume instead of the initial one
+        let mut connect_config = self.playback.connect_config.clone();
+        connect_config.initial_volume = self.cache.volume().or(connect_config.initial_volume);
+        let hellos = self.spirc_hellos.subscribe();
+        self.start_spirc(&session, connect_config);
+
+        if let (Some(state), Some(remote)) = (state, &mut self.remote) {
+            self.handle.spawn(remote.restore(state, hellos));
+        }
     }
 
     fn shutdown(&mut self) {
+        self.stopped = true;
         self.listener.notify(LibrespotEvent::Shutdown);
+        if let Some(discovery) = self.discovery.take() {
+            discovery.abort();
+        }
         if let Some(ref spirc) = self.spirc {
             spirc.shutdown();
         }
@@ -226,26 +825,154 @@ impl LibrespotController {
         }
     }
 
-    async fn autoreconnect(&mut self) -> bool {
-        warn!("Spirc shut down unexpectedly");
-        self.listener.notify(LibrespotEvent::StartReconnect);
+    /// Tear down the session, so that it can be replaced by a new one
+    fn disconnect(&mut self) {
+        // invalidates the Spirc and scheduled reconnects
+        self.spirc_generation += 1;
+        self.token_requests = None;
+        self.remote = None;
+        self.mixer = None;
+        if let Some(spirc) = self.spirc.take() {
+            spirc.shutdown();
+        }
+        if let Some(session) = self.session.take() {
+            session.shutdown();
+        }
+    }
 
-        let now = Instant::now();
-        while (!self.auto_connect_times.is_empty())
-            && ((now - self.auto_connect_times[0]).as_secs() > 600)
-        {
-            let _ = self.auto_connect_times.remove(0);
+    fn set_network(&mut self, online: bool, metered: bool) {
+        info!("Network changed (online: {}, metered: {})", online, metered);
+        self.online = online;
+        self.metered = metered;
+    }
+
+    /// Reconnect for the new network, at a lower bitrate on a metered one
+    ///
+    /// Returns `false` when the runtime should stop.
+    async fn network_changed(&mut self, online: bool, metered: bool) -> bool {
+        self.set_network(online, metered);
+        if self.credentials.is_none() {
+            // still waiting for a login
+            return true;
+        }
+
+        if !online {
+            // the connection is lost anyway, reconnect when the network is back
+            if self.session.is_some() {
+                self.disconnect();
+                self.listener.notify(LibrespotEvent::StartReconnect);
+            }
+            return true;
+        }
+
+        // the old connection might hang on the new network, so always connect again
+        let state = self.spirc_state.lock().unwrap().take();
+        self.disconnect();
+        self.backoff.reset();
+        let hellos = self.spirc_hellos.subscribe();
+        if !self.connect().await {
+            return false;
         }
 
-        if self.auto_connect_times.len() >= 5 {
-            warn!("Spirc shut down too often. Not reconnecting automatically.");
-            self.listener.notify(LibrespotEvent::ConnectionError {
-                message: "Spirc shut down too often. Not reconnecting automatically.".to_string(),
+        if let (Some(state), Some(remote)) = (state, &mut self.remote) {
+            self.handle.spawn(remote.restore(state, hellos));
+        }
+        true
+    }
+
+    /// Reconnect now or, if offline, as soon as the network is back
+    ///
+    /// Returns `false` when the reconnect policy gives up.
+    async fn reconnect(&mut self) -> bool {
+        if !self.online {
+            info!("Offline, waiting for the network before reconnecting");
+            return true;
+        }
+
+        self.connect().await
+    }
+
+    /// Log in, starting over if the network changes meanwhile
+    ///
+    /// A failed login is retried according to the reconnect policy. Returns `false` when the
+    /// runtime should stop.
+    async fn connect(&mut self) -> bool {
+        loop {
+            match self.login().await {
+                Ok(()) => return true,
+                Err(LoginFailed::NetworkChanged) => {
+                    self.disconnect();
+                    if !self.online {
+                        info!("Offline, waiting for the network before reconnecting");
+                        self.listener.notify(LibrespotEvent::StartReconnect);
+                        return true;
+                    }
+                    self.backoff.reset();
+                }
+                Err(LoginFailed::Rejected) => return true,
+                Err(err) => return err.retryable() && self.schedule_reconnect(),
+            }
+        }
+    }
+
+    /// Schedule the next reconnect according to the reconnect policy
+    ///
+    /// Returns `false` when the policy gives up.
+    fn schedule_reconnect(&mut self) -> bool {
+        if !self.online {
+            // don't use up attempts while there is no network
+            return true;
+        }
+
+        let ReconnectAttempt { attempt, delay } = match self.backoff.next_attempt() {
+            Some(attempt) => attempt,
+            None => {
+                warn!("Reconnecting failed too often. Not reconnecting automatically.");
+                self.listener.notify(LibrespotEvent::ConnectionError {
+                    message: "Reconnecting failed too often. Not reconnecting automatically."
+                        .to_string(),
+                });
+                return false;
+            }
+        };
+
+        info!("Reconnect attempt {} in {:?}", attempt, delay);
+        self.listener.notify(LibrespotEvent::ReconnectScheduled {
+            attempt,
+            delay_ms: delay.as_millis() as u64,
+        });
+
+        let generation = self.spirc_generation;
+        let control_tx = self.control_tx.clone();
+        self.handle.spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = control_tx.unbounded_send(ControlMessage::Reconnect { generation });
+        });
+        true
+    }
+}
+
+/// Result of a spawned command, reported as failed if the task is dropped with the runtime
+struct PendingResult {
+    request_id: RequestId,
+    command: &'static str,
+    listener: Option<LibrespotEventListenerRef>,
+}
+
+impl PendingResult {
+    fn finish(&mut self, result: CommandResult) {
+        if let Some(listener) = self.listener.take() {
+            listener.notify(LibrespotEvent::CommandResult {
+                request_id: self.request_id,
+                command: self.command,
+                result,
             });
-            false
-        } else {
-            self.auto_connect_times.push(now);
-            self.login().await
         }
     }
 }
+
+impl Drop for PendingResult {
+    fn drop(&mut self) {
+        self.finish(Err(CommandError::NotRunning));
+    }
+}
@@ -0,0 +1,451 @@
+//! Credential storage
+//!
+//! Reusable credentials and the credentials of the proxy are kept in a [`CredentialStore`]. The
+//! default store encrypts them with a key derived from a random key file and the machine id, so a
+//! copy of the config directory is useless on another device.
+//!
+//! librespot writes the reusable credentials as plaintext `credentials.json` into the cache
+//! directory while logging in, and only hands them out through that file. [`CredentialManager`]
+//! moves them into the store right after the login and wipes the file, which also migrates files
+//! of older versions. Until then the file is only protected by [`create_private_dir`].
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use librespot_core::authentication::Credentials;
+use log::{info, warn};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::player::error::{LibrespotError, LibrespotResult};
+use crate::player::secret::{zeroize, zeroize_credentials, SecretString};
+
+pub const PLAINTEXT_FILE: &str = "credentials.json";
+pub const ENCRYPTED_FILE: &str = "credentials.enc";
+pub const KEY_FILE: &str = "credentials.key";
+pub const PROXY_FILE: &str = "proxy.enc";
+
+const KEY_LEN: usize = 32;
+const MACHINE_ID_FILE: &str = "/etc/machine-id";
+const KEY_INFO: &[u8] = b"sailify credentials";
+const AAD: &[u8] = b"sailify-credentials-v1";
+const PROXY_AAD: &[u8] = b"sailify-proxy-v1";
+
+/// Storage for reusable credentials
+pub trait CredentialStore: RefUnwindSafe + UnwindSafe + Send + Sync {
+    /// Stored credentials, `None` if there are none
+    fn load(&self) -> LibrespotResult<Option<Credentials>>;
+
+    fn save(&self, credentials: &Credentials) -> LibrespotResult<()>;
+
+    /// Remove the credentials, so that they can not be recovered
+    fn remove(&self) -> LibrespotResult<()>;
+
+    /// Stored user info (`user:password`) of the proxy URL, `None` if there is none
+    fn load_proxy_user_info(&self) -> LibrespotResult<Option<SecretString>>;
+
+    /// Store the user info of the proxy URL, `None` removes it
+    fn save_proxy_user_info(&self, user_info: Option<&SecretString>) -> LibrespotResult<()>;
+}
+
+pub type CredentialStoreRef = Arc<dyn CredentialStore>;
+
+/// Creates the credential store of a profile
+pub type CredentialStoreFactory = Arc<dyn Fn(&str) -> CredentialStoreRef + Send + Sync>;
+
+/// Encrypts the credentials with AES-256-GCM and a device bound key
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    /// Credentials of the proxy, next to `path`
+    proxy_path: PathBuf,
+    key_path: PathBuf,
+    machine_id_path: PathBuf,
+}
+
+impl EncryptedFileStore {
+    /// Store the credentials at `path` with the key file at `key_path`
+    #[must_use]
+    pub fn new(path: PathBuf, key_path: PathBuf) -> Self {
+        Self {
+            proxy_path: path.with_file_name(PROXY_FILE),
+            path,
+            key_path,
+            machine_id_path: PathBuf::from(MACHINE_ID_FILE),
+        }
+    }
+
+    fn key(&self) -> LibrespotResult<LessSafeKey> {
+        let secret = self.read_or_create_key_file()?;
+        let machine_id = match fs::read_to_string(&self.machine_id_path) {
+            Ok(machine_id) => machine_id.trim().to_string(),
+            Err(err) => {
+                warn!(
+                    "Could not read machine id, key is not bound to the device: {}",
+                    err
+                );
+                String::new()
+            }
+        };
+
+        let key = Salt::new(HKDF_SHA256, machine_id.as_bytes())
+            .extract(&secret)
+            .expand(&[KEY_INFO], &AES_256_GCM)
+            .map(UnboundKey::from)
+            .map_err(|_| crypto_error("Could not derive key"))?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    /// Decrypted content of `path`, `None` if there is no such file
+    ///
+    /// The caller wipes the plaintext.
+    fn read_encrypted(&self, path: &Path, aad: &'static [u8]) -> LibrespotResult<Option<Vec<u8>>> {
+        let mut data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if data.len() < NONCE_LEN {
+            return Err(crypto_error("Encrypted file is corrupt"));
+        }
+
+        let mut ciphertext = data.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&data)
+            .map_err(|_| crypto_error("Encrypted file is corrupt"))?;
+        let len = self
+            .key()?
+            .open_in_place(nonce, Aad::from(aad), &mut ciphertext)
+            .map_err(|_| crypto_error("Could not decrypt credentials"))?
+            .len();
+        ciphertext.truncate(len);
+        Ok(Some(ciphertext))
+    }
+
+    /// Encrypt `data` into `path`, wiping `data`
+    fn write_encrypted(
+        &self,
+        path: &Path,
+        aad: &'static [u8],
+        mut data: Vec<u8>,
+    ) -> LibrespotResult<()> {
+        let key = self.key()?;
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| crypto_error("Could not generate nonce"))?;
+
+        let result = key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(aad),
+            &mut data,
+        );
+        if result.is_err() {
+            zeroize(&mut data);
+            return Err(crypto_error("Could not encrypt credentials"));
+        }
+
+        let mut file_data = nonce.to_vec();
+        file_data.append(&mut data);
+        write_private(path, &file_data)?;
+        Ok(())
+    }
+
+    fn read_or_create_key_file(&self) -> LibrespotResult<Vec<u8>> {
+        match fs::read(&self.key_path) {
+            Ok(secret) if secret.len() == KEY_LEN => return Ok(secret),
+            Ok(_) => return Err(crypto_error("Key file is corrupt")),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
+        }
+
+        info!("Creating credentials key");
+        let mut secret = vec![0u8; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .map_err(|_| crypto_error("Could not generate key"))?;
+        write_private(&self.key_path, &secret)?;
+        Ok(secret)
+    }
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn load(&self) -> LibrespotResult<Option<Credentials>> {
+        let mut plaintext = match self.read_encrypted(&self.path, AAD)? {
+            Some(plaintext) => plaintext,
+            None => return Ok(None),
+        };
+        let credentials = serde_json::from_slice(&plaintext).map_err(io::Error::from);
+        zeroize(&mut plaintext);
+        Ok(Some(credentials?))
+    }
+
+    fn save(&self, credentials: &Credentials) -> LibrespotResult<()> {
+        let data = serde_json::to_vec(credentials).map_err(io::Error::from)?;
+        self.write_encrypted(&self.path, AAD, data)
+    }
+
+    fn remove(&self) -> LibrespotResult<()> {
+        wipe_file(&self.path)?;
+        Ok(())
+    }
+
+    fn load_proxy_user_info(&self) -> LibrespotResult<Option<SecretString>> {
+        let plaintext = match self.read_encrypted(&self.proxy_path, PROXY_AAD)? {
+            Some(plaintext) => plaintext,
+            None => return Ok(None),
+        };
+        match String::from_utf8(plaintext) {
+            Ok(user_info) => Ok(Some(SecretString::new(user_info))),
+            Err(err) => {
+                zeroize(&mut err.into_bytes());
+                Err(crypto_error("Proxy credentials file is corrupt"))
+            }
+        }
+    }
+
+    fn save_proxy_user_info(&self, user_info: Option<&SecretString>) -> LibrespotResult<()> {
+        match user_info {
+            Some(user_info) => self.write_encrypted(
+                &self.proxy_path,
+                PROXY_AAD,
+                user_info.expose().as_bytes().to_vec(),
+            ),
+            None => {
+                wipe_file(&self.proxy_path)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Keeps the credentials of librespot in a [`CredentialStore`]
+#[derive(Clone)]
+pub struct CredentialManager {
+    store: CredentialStoreRef,
+    /// File librespot writes the reusable credentials to
+    plaintext_path: Option<PathBuf>,
+}
+
+impl CredentialManager {
+    #[must_use]
+    pub fn new(store: CredentialStoreRef, plaintext_path: Option<PathBuf>) -> Self {
+        Self {
+            store,
+            plaintext_path,
+        }
+    }
+
+    /// Stored credentials, migrating a plaintext file first
+    pub fn load(&self) -> LibrespotResult<Option<Credentials>> {
+        self.take_over()?;
+        self.store.load()
+    }
+
+    /// Move credentials librespot wrote as plaintext into the store
+    pub fn take_over(&self) -> LibrespotResult<()> {
+        let path = match &self.plaintext_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let parsed = serde_json::from_slice::<Credentials>(&data);
+        zeroize(&mut data);
+        match parsed {
+            Ok(mut credentials) => {
+                info!("Moving plaintext credentials into the credential store");
+                let result = self.store.save(&credentials);
+                zeroize_credentials(&mut credentials);
+                result?;
+            }
+            Err(err) => warn!("Removing unreadable plaintext credentials: {}", err),
+        }
+        wipe_file(path)?;
+        Ok(())
+    }
+
+    /// Wipe the credentials from the store and a plaintext file
+    pub fn remove(&self) -> LibrespotResult<()> {
+        let result = self.store.remove();
+        if let Some(path) = &self.plaintext_path {
+            wipe_file(path)?;
+        }
+        result
+    }
+}
+
+/// Create a directory only the user can access, restricting an existing one
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+}
+
+/// Write a file only the user can access
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        create_private_dir(dir)?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Overwrite a file with zeros before removing it
+fn wipe_file(path: &Path) -> io::Result<()> {
+    let len = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    io::copy(&mut io::repeat(0).take(len), &mut file)?;
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)
+}
+
+fn crypto_error(message: &str) -> LibrespotError {
+    io::Error::new(io::ErrorKind::InvalidData, message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use librespot_protocol::authentication::AuthenticationType;
+    use tempfile::TempDir;
+
+    fn store(dir: &TempDir, machine_id: &str) -> EncryptedFileStore {
+        let machine_id_path = dir.path().join("machine-id");
+        fs::write(&machine_id_path, machine_id).unwrap();
+        EncryptedFileStore {
+            machine_id_path,
+            ..EncryptedFileStore::new(dir.path().join(ENCRYPTED_FILE), dir.path().join(KEY_FILE))
+        }
+    }
+
+    fn credentials() -> Credentials {
+        Credentials {
+            username: "user".to_string(),
+            auth_type: AuthenticationType::AUTHENTICATION_STORED_SPOTIFY_CREDENTIALS,
+            auth_data: b"reusable-secret".to_vec(),
+        }
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn round_trips_encrypted_credentials() {
+        let dir = TempDir::new().unwrap();
+        let store = store(&dir, "machine");
+        assert!(store.load().unwrap().is_none());
+
+        store.save(&credentials()).unwrap();
+        let file = fs::read(dir.path().join(ENCRYPTED_FILE)).unwrap();
+        assert!(!file.windows(15).any(|w| w == b"reusable-secret"));
+        assert_eq!(mode(&dir.path().join(ENCRYPTED_FILE)), 0o600);
+        assert_eq!(mode(&dir.path().join(KEY_FILE)), 0o600);
+
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded.username, "user");
+        assert_eq!(loaded.auth_data, b"reusable-secret");
+
+        store.remove().unwrap();
+        assert!(!dir.path().join(ENCRYPTED_FILE).exists());
+        assert!(store.load().unwrap().is_none());
+    }
+
+    #[test]
+    fn key_is_bound_to_machine() {
+        let dir = TempDir::new().unwrap();
+        store(&dir, "machine").save(&credentials()).unwrap();
+
+        assert!(store(&dir, "other machine").load().is_err());
+    }
+
+    #[test]
+    fn round_trips_proxy_user_info() {
+        let dir = TempDir::new().unwrap();
+        let store = store(&dir, "machine");
+
+        let user_info = SecretString::new("user:pass".to_string());
+        store.save_proxy_user_info(Some(&user_info)).unwrap();
+        assert_eq!(
+            store.load_proxy_user_info().unwrap().unwrap().expose(),
+            "user:pass"
+        );
+        // separate from the credentials
+        assert!(store.load().unwrap().is_none());
+
+        store.save_proxy_user_info(None).unwrap();
+        assert!(store.load_proxy_user_info().unwrap().is_none());
+        assert!(!dir.path().join(PROXY_FILE).exists());
+    }
+
+    #[test]
+    fn moves_plaintext_credentials_into_store() {
+        let dir = TempDir::new().unwrap();
+        let plaintext_path = dir.path().join(PLAINTEXT_FILE);
+        fs::write(&plaintext_path, serde_json::to_vec(&credentials()).unwrap()).unwrap();
+        let manager = CredentialManager::new(
+            Arc::new(store(&dir, "machine")),
+            Some(plaintext_path.clone()),
+        );
+
+        let loaded = manager.load().unwrap().unwrap();
+        assert_eq!(loaded.username, "user");
+        assert_eq!(loaded.auth_data, b"reusable-secret");
+        assert!(!plaintext_path.exists());
+        // loaded from the store from now on
+        assert!(manager.load().unwrap().is_some());
+
+        manager.remove().unwrap();
+        assert!(manager.load().unwrap().is_none());
+    }
+
+    #[test]
+    fn removes_unreadable_plaintext_credentials() {
+        let dir = TempDir::new().unwrap();
+        let plaintext_path = dir.path().join(PLAINTEXT_FILE);
+        fs::write(&plaintext_path, b"{ broken").unwrap();
+        let manager = CredentialManager::new(
+            Arc::new(store(&dir, "machine")),
+            Some(plaintext_path.clone()),
+        );
+
+        assert!(manager.load().unwrap().is_none());
+        assert!(!plaintext_path.exists());
+    }
+
+    #[test]
+    fn restricts_existing_dir() {
+        let dir = TempDir::new().unwrap();
+        let cache = dir.path().join("cache");
+        fs::create_dir(&cache).unwrap();
+        fs::set_permissions(&cache, fs::Permissions::from_mode(0o755)).unwrap();
+
+        create_private_dir(&cache).unwrap();
+        assert_eq!(mode(&cache), 0o700);
+    }
+}
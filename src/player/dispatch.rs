@@ -0,0 +1,312 @@
+//! Event dispatch
+//!
+//! The runtime only queues its events. A dispatch thread delivers them to the listeners, so a
+//! slow listener can't stall playback control or the token refresh. Alternatively the host turns
+//! on polling and drains the queue on its own main loop.
+//!
+//! A position, volume or state event replaces the queued event of the same kind, only the latest
+//! value matters for these. These events are dropped first when the queue is full. Other events are
+//! queued beyond the capacity up to a hard limit, so that a stalled host can't exhaust the memory.
+//! At that limit the oldest event is dropped, sparing command results, the shutdown and panics as
+//! long as there is anything else to drop: command futures wait for their result.
+
+use std::collections::VecDeque;
+use std::mem::discriminant;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use log::{error, warn};
+
+use crate::player::events::{LibrespotEvent, LibrespotEventListener, LibrespotEventListenerRef};
+
+/// Events queued before position, volume and state events are dropped
+pub const EVENT_QUEUE_CAPACITY: usize = 256;
+/// Events queued at most, older events are dropped beyond
+pub const MAX_QUEUED_EVENTS: usize = 4 * EVENT_QUEUE_CAPACITY;
+
+#[derive(Default)]
+pub struct EventQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct QueueState {
+    events: VecDeque<LibrespotEvent>,
+    /// Events are delivered by `EventDispatcher::poll` instead of the thread
+    polled: bool,
+    closed: bool,
+    /// Events dropped or queued beyond the capacity since the queue was last empty
+    dropped: u64,
+    overflowed: u64,
+}
+
+/// Whether only the latest event of its kind matters
+fn is_coalesced(evt: &LibrespotEvent) -> bool {
+    matches!(
+        evt,
+        LibrespotEvent::Position { .. }
+            | LibrespotEvent::VolumeSet { .. }
+            | LibrespotEvent::StateChanged { .. }
+    )
+}
+
+/// Whether the event is dropped only if nothing else is left to drop
+fn is_essential(evt: &LibrespotEvent) -> bool {
+    matches!(
+        evt,
+        LibrespotEvent::CommandResult { .. }
+            | LibrespotEvent::Shutdown
+            | LibrespotEvent::Panic { .. }
+    )
+}
+
+impl QueueState {
+    fn pop(&mut self) -> Option<LibrespotEvent> {
+        let evt = self.events.pop_front();
+        if self.events.is_empty() && (self.dropped > 0 || self.overflowed > 0) {
+            warn!(
+                "Event queue recovered, {} events dropped, {} queued beyond capacity",
+                self.dropped, self.overflowed
+            );
+            self.dropped = 0;
+            self.overflowed = 0;
+        }
+        evt
+    }
+}
+
+impl EventQueue {
+    /// Next event for the dispatch thread, `None` when closed
+    fn next(&self) -> Option<LibrespotEvent> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if !state.polled {
+                if let Some(evt) = state.pop() {
+                    return Some(evt);
+                }
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+impl LibrespotEventListener for EventQueue {
+    fn notify(&self, evt: LibrespotEvent) {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+
+        let kind = discriminant(&evt);
+        if is_coalesced(&evt) {
+            if let Some(index) = state.events.iter().position(|e| discriminant(e) == kind) {
+                state.events.remove(index);
+            }
+        }
+        if state.events.len() >= EVENT_QUEUE_CAPACITY {
+            if state.dropped == 0 && state.overflowed == 0 {
+                warn!("Event queue is full");
+            }
+            if let Some(index) = state.events.iter().position(is_coalesced) {
+                state.events.remove(index);
+                state.dropped += 1;
+            } else if is_coalesced(&evt) {
+                state.dropped += 1;
+                return;
+            } else if state.events.len() >= MAX_QUEUED_EVENTS {
+                state.dropped += 1;
+                match state.events.iter().position(|e| !is_essential(e)) {
+                    Some(index) => {
+                        state.events.remove(index);
+                    }
+                    None if !is_essential(&evt) => return,
+                    None => {
+                        state.events.pop_front();
+                    }
+                }
+            } else {
+                state.overflowed += 1;
+            }
+        }
+
+        state.events.push_back(evt);
+        drop(state);
+        self.changed.notify_one();
+    }
+}
+
+/// Delivers the queued events to a listener
+pub struct EventDispatcher {
+    queue: Arc<EventQueue>,
+    listener: LibrespotEventListenerRef,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EventDispatcher {
+    #[must_use]
+    pub fn start(listener: LibrespotEventListenerRef) -> Self {
+        let queue = Arc::new(EventQueue::default());
+        let thread = {
+            let queue = queue.clone();
+            let listener = listener.clone();
+            thread::Builder::new()
+                .name("sailify-events".to_string())
+                .spawn(move || {
+                    while let Some(evt) = queue.next() {
+                        deliver(&*listener, evt);
+                    }
+                })
+                .unwrap()
+        };
+
+        Self {
+            queue,
+            listener,
+            thread: Some(thread),
+        }
+    }
+
+    /// Listener that queues the events
+    #[must_use]
+    pub fn queue(&self) -> Arc<EventQueue> {
+        self.queue.clone()
+    }
+
+    /// Deliver events only from `poll` instead of the dispatch thread
+    pub fn set_polled(&self, polled: bool) {
+        self.queue.state.lock().unwrap().polled = polled;
+        self.queue.changed.notify_all();
+    }
+
+    /// Deliver up to `max_events` queued events on the calling thread
+    ///
+    /// Returns the number of delivered events, always 0 unless polling is on.
+    pub fn poll(&self, max_events: usize) -> usize {
+        let mut delivered = 0;
+        while delivered < max_events {
+            let evt = {
+                let mut state = self.queue.state.lock().unwrap();
+                if !state.polled {
+                    break;
+                }
+                match state.pop() {
+                    Some(evt) => evt,
+                    None => break,
+                }
+            };
+            deliver(&*self.listener, evt);
+            delivered += 1;
+        }
+        delivered
+    }
+}
+
+impl Drop for EventDispatcher {
+    fn drop(&mut self) {
+        // the thread delivers the remaining events before it ends
+        self.queue.close();
+        if let Some(thread) = self.thread.take() {
+            // a listener may drop the player on the dispatch thread
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+fn deliver(listener: &dyn LibrespotEventListener, evt: LibrespotEvent) {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| listener.notify(evt)));
+    if result.is_err() {
+        error!("Event listener panicked");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::error::LibrespotError;
+
+    fn queue() -> EventQueue {
+        let queue = EventQueue::default();
+        queue.state.lock().unwrap().polled = true;
+        queue
+    }
+
+    fn drain(queue: &EventQueue) -> Vec<LibrespotEvent> {
+        let mut state = queue.state.lock().unwrap();
+        std::iter::from_fn(|| state.pop()).collect()
+    }
+
+    #[test]
+    fn keeps_only_latest_coalesced_event() {
+        let queue = queue();
+        queue.notify(LibrespotEvent::Position { position_ms: 1 });
+        queue.notify(LibrespotEvent::Connected);
+        queue.notify(LibrespotEvent::Position { position_ms: 2 });
+
+        assert!(matches!(
+            drain(&queue)[..],
+            [
+                LibrespotEvent::Connected,
+                LibrespotEvent::Position { position_ms: 2 }
+            ]
+        ));
+    }
+
+    #[test]
+    fn queues_other_events_beyond_capacity() {
+        let queue = queue();
+        queue.notify(LibrespotEvent::VolumeSet { volume: 1 });
+        for _ in 1..EVENT_QUEUE_CAPACITY {
+            queue.notify(LibrespotEvent::Connecting);
+        }
+        queue.notify(LibrespotEvent::Shutdown);
+        queue.notify(LibrespotEvent::Position { position_ms: 1 });
+        queue.notify(LibrespotEvent::Error {
+            err: LibrespotError::BadCredentials,
+        });
+        assert_eq!(queue.state.lock().unwrap().dropped, 2);
+
+        let events = drain(&queue);
+        assert_eq!(events.len(), EVENT_QUEUE_CAPACITY + 1);
+        assert!(events.iter().all(|evt| !is_coalesced(evt)));
+        assert!(matches!(
+            events[EVENT_QUEUE_CAPACITY - 1..],
+            [LibrespotEvent::Shutdown, LibrespotEvent::Error { .. }]
+        ));
+    }
+
+    #[test]
+    fn stays_bounded_while_the_host_stalls() {
+        let queue = queue();
+        queue.notify(LibrespotEvent::CommandResult {
+            request_id: 1,
+            command: "play",
+            result: Ok(()),
+        });
+        for position_ms in 0..10 * MAX_QUEUED_EVENTS as u32 {
+            queue.notify(LibrespotEvent::StartReconnect);
+            queue.notify(LibrespotEvent::Position { position_ms });
+            assert!(queue.state.lock().unwrap().events.len() <= MAX_QUEUED_EVENTS);
+        }
+        queue.notify(LibrespotEvent::Shutdown);
+
+        let events = drain(&queue);
+        assert_eq!(events.len(), MAX_QUEUED_EVENTS);
+        assert!(matches!(
+            events[0],
+            LibrespotEvent::CommandResult { request_id: 1, .. }
+        ));
+        assert!(matches!(events.last(), Some(LibrespotEvent::Shutdown)));
+    }
+}
@@ -21,6 +21,38 @@ quick_error! {
             display("Connection error: {}", msg)
         }
 
+        BadCredentials {
+            display("Invalid username or password")
+        }
+
+        NetworkUnreachable(msg: String) {
+            display("Network is unreachable: {}", msg)
+        }
+
+        AccessPointRefused(msg: String) {
+            display("Access point refused the connection: {}", msg)
+        }
+
+        Timeout(msg: String) {
+            display("Timed out: {}", msg)
+        }
+
+        RateLimited(msg: String) {
+            display("Too many requests: {}", msg)
+        }
+
+        AccountRestricted(msg: String) {
+            display("Account is restricted: {}", msg)
+        }
+
+        PremiumRequired {
+            display("A Spotify Premium account is required")
+        }
+
+        OAuth(msg: String) {
+            display("OAuth login failed: {}", msg)
+        }
+
         Panic(msg: String) {
             display("Internal error: {}", msg)
         }
@@ -29,7 +61,55 @@ quick_error! {
 
 pub type LibrespotResult<T> = Result<T, LibrespotError>;
 
+impl Clone for LibrespotError {
+    fn clone(&self) -> Self {
+        match self {
+            // I/O errors can't be cloned, keep what is displayed
+            LibrespotError::Io(err) => {
+                LibrespotError::Io(std::io::Error::new(err.kind(), err.to_string()))
+            }
+            LibrespotError::MissingCredentials => LibrespotError::MissingCredentials,
+            LibrespotError::IllegalConfig(msg) => LibrespotError::IllegalConfig(msg.clone()),
+            LibrespotError::Connection(msg) => LibrespotError::Connection(msg.clone()),
+            LibrespotError::BadCredentials => LibrespotError::BadCredentials,
+            LibrespotError::NetworkUnreachable(msg) => {
+                LibrespotError::NetworkUnreachable(msg.clone())
+            }
+            LibrespotError::AccessPointRefused(msg) => {
+                LibrespotError::AccessPointRefused(msg.clone())
+            }
+            LibrespotError::Timeout(msg) => LibrespotError::Timeout(msg.clone()),
+            LibrespotError::RateLimited(msg) => LibrespotError::RateLimited(msg.clone()),
+            LibrespotError::AccountRestricted(msg) => {
+                LibrespotError::AccountRestricted(msg.clone())
+            }
+            LibrespotError::PremiumRequired => LibrespotError::PremiumRequired,
+            LibrespotError::OAuth(msg) => LibrespotError::OAuth(msg.clone()),
+            LibrespotError::Panic(msg) => LibrespotError::Panic(msg.clone()),
+        }
+    }
+}
+
 impl LibrespotError {
+    /// Whether the error ended a connection or the login
+    ///
+    /// A rate limit is only reported by the login, which leaves the player disconnected too.
+    #[must_use]
+    pub fn is_connection_error(&self) -> bool {
+        matches!(
+            self,
+            LibrespotError::MissingCredentials
+                | LibrespotError::Connection(_)
+                | LibrespotError::BadCredentials
+                | LibrespotError::NetworkUnreachable(_)
+                | LibrespotError::AccessPointRefused(_)
+                | LibrespotError::Timeout(_)
+                | LibrespotError::AccountRestricted(_)
+                | LibrespotError::PremiumRequired
+                | LibrespotError::RateLimited(_)
+        )
+    }
+
     #[must_use]
     pub fn kind(&self) -> &'static str {
         match self {
@@ -37,6 +117,14 @@ impl LibrespotError {
             LibrespotError::IllegalConfig(_) => "illegal-config",
             LibrespotError::Io(_) => "io",
             LibrespotError::Connection(_) => "connection",
+            LibrespotError::BadCredentials => "bad-credentials",
+            LibrespotError::NetworkUnreachable(_) => "network-unreachable",
+            LibrespotError::AccessPointRefused(_) => "ap-refused",
+            LibrespotError::Timeout(_) => "timeout",
+            LibrespotError::RateLimited(_) => "rate-limited",
+            LibrespotError::AccountRestricted(_) => "account-restricted",
+            LibrespotError::PremiumRequired => "premium-required",
+            LibrespotError::OAuth(_) => "oauth",
             LibrespotError::Panic(_) => "panic",
         }
     }
@@ -1,12 +1,37 @@
 use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::sync::Arc;
 
-use librespot_core::keymaster::Token;
 use librespot_playback::player::PlayerEvent;
 
+use crate::player::account::AccountInfo;
+use crate::player::commands::{CommandResult, RequestId};
 use crate::player::error::LibrespotError;
+use crate::player::login::LoginStage;
+use crate::player::state::StateSnapshot;
+use crate::player::token::AccessToken;
+use crate::player::{RepeatMode, Setting};
 
-#[derive(Debug)]
+/// Bit set of event categories a listener receives
+pub type EventCategories = u32;
+
+/// Track changes and playback state: stopped, changed, loading, playing, paused, unavailable
+pub const TRACK_EVENTS: EventCategories = 1;
+pub const POSITION_EVENTS: EventCategories = 1 << 1;
+pub const VOLUME_EVENTS: EventCategories = 1 << 2;
+/// Shuffle and repeat
+pub const MODE_EVENTS: EventCategories = 1 << 3;
+/// Connecting, connected, connection errors, reconnects and shutdown
+pub const CONNECTION_EVENTS: EventCategories = 1 << 4;
+/// Login progress, OAuth, account info and access tokens
+pub const LOGIN_EVENTS: EventCategories = 1 << 5;
+pub const COMMAND_EVENTS: EventCategories = 1 << 6;
+pub const STATE_EVENTS: EventCategories = 1 << 7;
+pub const SETTINGS_EVENTS: EventCategories = 1 << 8;
+/// Errors and panics of the runtime
+pub const ERROR_EVENTS: EventCategories = 1 << 9;
+pub const ALL_EVENTS: EventCategories = 0xFFFFFFFF;
+
+#[derive(Clone, Debug)]
 pub enum LibrespotEvent {
     Stopped {
         play_request_id: u64,
@@ -36,28 +61,104 @@ pub enum LibrespotEvent {
         play_request_id: u64,
         track_id: String,
     },
+    /// Interpolated position, reported periodically while playing
+    Position {
+        position_ms: u32,
+    },
     VolumeSet {
         volume: u16,
     },
+    ShuffleChanged {
+        shuffle: bool,
+    },
+    RepeatChanged {
+        repeat: RepeatMode,
+    },
     Connecting,
+    /// A playback command is done or failed
+    CommandResult {
+        request_id: RequestId,
+        command: &'static str,
+        result: CommandResult,
+    },
+    /// A login moved on to the next stage
+    LoginProgress {
+        stage: LoginStage,
+    },
     Connected,
     ConnectionError {
         message: String,
     },
     Shutdown,
     StartReconnect,
+    ReconnectScheduled {
+        attempt: u32,
+        delay_ms: u64,
+    },
     TokenChanged {
-        token: Result<Token, String>,
+        token: Result<AccessToken, String>,
     },
     Error {
         err: LibrespotError,
     },
+    StateChanged {
+        state: StateSnapshot,
+    },
+    /// OAuth login succeeded, the player can be started
+    OAuthCompleted,
+    /// Account of the session, reported after connecting
+    AccountInfo {
+        info: AccountInfo,
+    },
+    /// Started without a login, waiting for a Connect app to hand one over
+    WaitingForDiscovery,
+    /// A Connect app handed over a login, connecting follows
+    RemoteLogin {
+        username: String,
+    },
+    SettingsApplied {
+        applied: Vec<Setting>,
+        restart: Vec<Setting>,
+    },
     Panic {
         message: String,
     },
 }
 
 impl LibrespotEvent {
+    #[must_use]
+    pub fn category(&self) -> EventCategories {
+        match self {
+            LibrespotEvent::Stopped { .. }
+            | LibrespotEvent::Changed { .. }
+            | LibrespotEvent::Loading { .. }
+            | LibrespotEvent::Playing { .. }
+            | LibrespotEvent::Paused { .. }
+            | LibrespotEvent::Unavailable { .. } => TRACK_EVENTS,
+            LibrespotEvent::Position { .. } => POSITION_EVENTS,
+            LibrespotEvent::VolumeSet { .. } => VOLUME_EVENTS,
+            LibrespotEvent::ShuffleChanged { .. } | LibrespotEvent::RepeatChanged { .. } => {
+                MODE_EVENTS
+            }
+            LibrespotEvent::Connecting
+            | LibrespotEvent::Connected
+            | LibrespotEvent::ConnectionError { .. }
+            | LibrespotEvent::Shutdown
+            | LibrespotEvent::StartReconnect
+            | LibrespotEvent::WaitingForDiscovery
+            | LibrespotEvent::ReconnectScheduled { .. } => CONNECTION_EVENTS,
+            LibrespotEvent::LoginProgress { .. }
+            | LibrespotEvent::OAuthCompleted
+            | LibrespotEvent::AccountInfo { .. }
+            | LibrespotEvent::RemoteLogin { .. }
+            | LibrespotEvent::TokenChanged { .. } => LOGIN_EVENTS,
+            LibrespotEvent::CommandResult { .. } => COMMAND_EVENTS,
+            LibrespotEvent::StateChanged { .. } => STATE_EVENTS,
+            LibrespotEvent::SettingsApplied { .. } => SETTINGS_EVENTS,
+            LibrespotEvent::Error { .. } | LibrespotEvent::Panic { .. } => ERROR_EVENTS,
+        }
+    }
+
     #[must_use]
     pub fn from_event(evt: PlayerEvent) -> Option<Self> {
         Some(match evt {
@@ -0,0 +1,296 @@
+//! Async Rust API
+//!
+//! [`PlayerHandle`] drives a [`SailifyPlayer`] from async Rust code: playback commands resolve
+//! with their result and events are delivered as [`EventStream`]s. The C ABI is a layer over the
+//! same handle that delivers events to callbacks instead. Neither needs a particular executor,
+//! the player runs its own runtime and calls that block run on a thread of their own.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use sailifyplayer::player::events::ALL_EVENTS;
+//! use sailifyplayer::player::handle::PlayerHandle;
+//!
+//! # async fn example() {
+//! let handle = PlayerHandle::new();
+//! let mut events = handle.events(ALL_EVENTS);
+//! handle.start().await;
+//! handle.play().await.expect("playing");
+//! while let Some(event) = events.next().await {
+//!     println!("{:?}", event);
+//! }
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::channel::oneshot;
+use futures::Stream;
+
+use crate::player::commands::{Command, CommandError, CommandResult, RequestId};
+use crate::player::dispatch::EventDispatcher;
+use crate::player::events::{
+    EventCategories, LibrespotEvent, LibrespotEventListener, LibrespotEventListenerRef, ALL_EVENTS,
+};
+use crate::player::listeners::{ListenerRegistry, SubscriptionId};
+use crate::player::volume;
+use crate::player::{RepeatMode, SailifyPlayer};
+
+/// Shareable handle of a player
+#[derive(Clone)]
+pub struct PlayerHandle {
+    player: Arc<Mutex<SailifyPlayer>>,
+    hub: Arc<EventHub>,
+    /// Listeners and dispatcher of the player, used without its lock because listeners may call
+    /// into the player
+    listeners: Arc<ListenerRegistry>,
+    dispatcher: Arc<EventDispatcher>,
+}
+
+impl PlayerHandle {
+    /// Player with the settings of the active profile
+    #[must_use]
+    pub fn new() -> Self {
+        let hub = Arc::new(EventHub::default());
+        let player = SailifyPlayer::new(hub.clone());
+        Self {
+            listeners: player.listeners(),
+            dispatcher: player.dispatcher(),
+            player: Arc::new(Mutex::new(player)),
+            hub,
+        }
+    }
+
+    /// Player with `listener` subscribed to all events
+    #[must_use]
+    pub fn with_listener(listener: LibrespotEventListenerRef) -> Self {
+        let handle = Self::new();
+        handle.subscribe(listener, ALL_EVENTS);
+        handle
+    }
+
+    /// Add a listener for the events in `categories`, see [`ListenerRegistry::subscribe`]
+    pub fn subscribe(
+        &self,
+        listener: LibrespotEventListenerRef,
+        categories: EventCategories,
+    ) -> SubscriptionId {
+        self.listeners.subscribe(listener, categories)
+    }
+
+    pub fn unsubscribe(&self, subscription: SubscriptionId) -> bool {
+        self.listeners.unsubscribe(subscription)
+    }
+
+    /// See [`SailifyPlayer::set_event_polling`]
+    pub fn set_event_polling(&self, enabled: bool) {
+        self.dispatcher.set_polled(enabled);
+    }
+
+    /// Deliver up to `max_events` queued events, returns the number of delivered events
+    pub fn poll_events(&self, max_events: usize) -> usize {
+        self.dispatcher.poll(max_events)
+    }
+
+    /// Call `f` with the player, for settings and profiles
+    ///
+    /// The player is locked meanwhile, `f` must neither block nor deliver events. Use the
+    /// functions of the handle to start or stop the player and for events.
+    pub fn with_player<R>(&self, f: impl FnOnce(&mut SailifyPlayer) -> R) -> R {
+        f(&mut self.player.lock().unwrap())
+    }
+
+    /// Call `f` with the player on a thread of its own and wait for its result
+    async fn with_player_blocking<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut SailifyPlayer) -> R + Send + 'static,
+    ) -> R {
+        let player = self.player.clone();
+        let (tx, rx) = oneshot::channel();
+        thread::spawn(move || {
+            let _ = tx.send(f(&mut player.lock().unwrap()));
+        });
+        rx.await.expect("player call panicked")
+    }
+
+    /// Start the player, see [`SailifyPlayer::start`]
+    pub async fn start(&self) -> bool {
+        self.with_player_blocking(SailifyPlayer::start).await
+    }
+
+    /// Stop the player and wait for its runtime to end
+    pub async fn stop(&self) {
+        self.with_player_blocking(SailifyPlayer::stop).await;
+    }
+
+    /// Forget the login and stop the player
+    pub async fn logout(&self) {
+        self.with_player_blocking(SailifyPlayer::logout).await;
+    }
+
+    /// Switch to the profile `name`, see [`SailifyPlayer::switch_profile`]
+    pub async fn switch_profile(&self, name: String) -> bool {
+        self.with_player_blocking(move |player| player.switch_profile(&name))
+            .await
+    }
+
+    /// Events in `categories` from now on, after a replay of the latest ones
+    #[must_use]
+    pub fn events(&self, categories: EventCategories) -> EventStream {
+        let (tx, rx) = unbounded();
+        let subscription = self.subscribe(Arc::new(StreamListener(Mutex::new(tx))), categories);
+        EventStream {
+            events: rx,
+            subscription,
+            listeners: Arc::downgrade(&self.listeners),
+        }
+    }
+
+    /// Send a playback command and wait for its result
+    pub async fn command(&self, command: Command) -> CommandResult {
+        let result = self.with_player(|player| {
+            let request_id = player.new_request_id();
+            let (tx, rx) = oneshot::channel();
+            // registered before sending, failures to send are reported right away
+            self.hub.pending.lock().unwrap().insert(request_id, tx);
+            player.send_command(request_id, command);
+            rx
+        });
+        result.await.unwrap_or(Err(CommandError::NotRunning))
+    }
+
+    pub async fn play(&self) -> CommandResult {
+        self.command(Command::Play).await
+    }
+
+    pub async fn pause(&self) -> CommandResult {
+        self.command(Command::Pause).await
+    }
+
+    pub async fn next(&self) -> CommandResult {
+        self.command(Command::Next).await
+    }
+
+    pub async fn previous(&self) -> CommandResult {
+        self.command(Command::Previous).await
+    }
+
+    pub async fn seek(&self, position_ms: u32) -> CommandResult {
+        self.command(Command::Seek { position_ms }).await
+    }
+
+    /// Set the volume in percent (0-100)
+    pub async fn set_volume(&self, percent: u16) -> CommandResult {
+        self.command(Command::SetVolume {
+            volume: volume::percent_to_raw(percent),
+        })
+        .await
+    }
+
+    pub async fn volume_up(&self) -> CommandResult {
+        self.command(Command::VolumeUp).await
+    }
+
+    pub async fn volume_down(&self) -> CommandResult {
+        self.command(Command::VolumeDown).await
+    }
+
+    pub async fn set_shuffle(&self, shuffle: bool) -> CommandResult {
+        self.command(Command::SetShuffle { shuffle }).await
+    }
+
+    pub async fn set_repeat(&self, repeat: RepeatMode) -> CommandResult {
+        self.command(Command::SetRepeat { repeat }).await
+    }
+
+    /// Load a context (album, playlist, ...) or a list of tracks and start playing
+    ///
+    /// The tracks of the context are resolved when `track_uris` is empty.
+    pub async fn load(
+        &self,
+        context_uri: String,
+        track_uris: Vec<String>,
+        start_index: u32,
+        position_ms: u32,
+        shuffle: bool,
+    ) -> CommandResult {
+        self.command(Command::Load {
+            context_uri,
+            track_uris,
+            start_index,
+            position_ms,
+            shuffle,
+        })
+        .await
+    }
+}
+
+impl Default for PlayerHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Events of a player, ends when the player is dropped
+///
+/// Unsubscribes when dropped.
+pub struct EventStream {
+    events: UnboundedReceiver<LibrespotEvent>,
+    subscription: SubscriptionId,
+    listeners: Weak<ListenerRegistry>,
+}
+
+impl Stream for EventStream {
+    type Item = LibrespotEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.events).poll_next(cx)
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        if let Some(listeners) = self.listeners.upgrade() {
+            listeners.unsubscribe(self.subscription);
+        }
+    }
+}
+
+struct StreamListener(Mutex<UnboundedSender<LibrespotEvent>>);
+
+impl LibrespotEventListener for StreamListener {
+    fn notify(&self, evt: LibrespotEvent) {
+        let _ = self.0.lock().unwrap().unbounded_send(evt);
+    }
+}
+
+/// Hands command results to the command futures
+#[derive(Default)]
+struct EventHub {
+    /// Command futures waiting for their result
+    pending: Mutex<HashMap<RequestId, oneshot::Sender<CommandResult>>>,
+}
+
+impl LibrespotEventListener for EventHub {
+    fn notify(&self, evt: LibrespotEvent) {
+        match &evt {
+            LibrespotEvent::CommandResult {
+                request_id, result, ..
+            } => {
+                if let Some(tx) = self.pending.lock().unwrap().remove(request_id) {
+                    let _ = tx.send(result.clone());
+                }
+            }
+            LibrespotEvent::Shutdown | LibrespotEvent::Panic { .. } => {
+                for (_, tx) in self.pending.lock().unwrap().drain() {
+                    let _ = tx.send(Err(CommandError::NotRunning));
+                }
+            }
+            _ => (),
+        }
+    }
+}
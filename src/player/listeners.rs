@@ -0,0 +1,201 @@
+//! Event listeners
+//!
+//! Any number of listeners can subscribe to the events of a player, each to the categories it
+//! needs. A new listener first receives the latest track, connection and volume events, so it
+//! can show the current state without waiting for the next change. An error that ended the
+//! connection counts as connection event, it is replayed to listeners of errors.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::player::events::{
+    EventCategories, LibrespotEvent, LibrespotEventListener, LibrespotEventListenerRef,
+};
+
+/// Identifies a listener for unsubscribing, never 0
+pub type SubscriptionId = u64;
+
+#[derive(Default)]
+pub struct ListenerRegistry {
+    inner: Mutex<Registry>,
+}
+
+#[derive(Default)]
+struct Registry {
+    last_id: SubscriptionId,
+    subscriptions: Vec<Arc<Subscription>>,
+    /// Latest events per replayed category
+    latest_track: Option<LibrespotEvent>,
+    latest_connection: Option<LibrespotEvent>,
+    latest_volume: Option<LibrespotEvent>,
+}
+
+struct Subscription {
+    id: SubscriptionId,
+    categories: EventCategories,
+    listener: LibrespotEventListenerRef,
+    active: AtomicBool,
+    /// Held while delivering, so that events can't overtake the replay
+    delivery: Mutex<()>,
+}
+
+impl ListenerRegistry {
+    /// Add a listener for the events in `categories`
+    ///
+    /// The latest events of the requested categories are replayed before this returns.
+    pub fn subscribe(
+        &self,
+        listener: LibrespotEventListenerRef,
+        categories: EventCategories,
+    ) -> SubscriptionId {
+        let mut inner = self.inner.lock().unwrap();
+        inner.last_id += 1;
+        let subscription = Arc::new(Subscription {
+            id: inner.last_id,
+            categories,
+            listener,
+            active: AtomicBool::new(true),
+            delivery: Mutex::new(()),
+        });
+        let delivery = subscription.delivery.lock().unwrap();
+        inner.subscriptions.push(subscription.clone());
+        let replay = inner.replay(categories);
+        drop(inner);
+
+        for evt in replay {
+            subscription.listener.notify(evt);
+        }
+        drop(delivery);
+        subscription.id
+    }
+
+    /// Remove a listener, returns `false` if there is none with this ID
+    ///
+    /// An event that is being delivered right now may still reach the listener. The listener is
+    /// dropped after its last event.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let index = inner
+            .subscriptions
+            .iter()
+            .position(|subscription| subscription.id == id);
+        match index {
+            Some(index) => {
+                let subscription = inner.subscriptions.remove(index);
+                subscription.active.store(false, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Registry {
+    fn remember(&mut self, evt: &LibrespotEvent) {
+        let latest = match evt {
+            LibrespotEvent::Stopped { .. }
+            | LibrespotEvent::Loading { .. }
+            | LibrespotEvent::Playing { .. }
+            | LibrespotEvent::Paused { .. }
+            | LibrespotEvent::Unavailable { .. } => &mut self.latest_track,
+            LibrespotEvent::Connecting
+            | LibrespotEvent::Connected
+            | LibrespotEvent::ConnectionError { .. }
+            | LibrespotEvent::Shutdown
+            | LibrespotEvent::StartReconnect
+            | LibrespotEvent::WaitingForDiscovery
+            | LibrespotEvent::ReconnectScheduled { .. } => &mut self.latest_connection,
+            LibrespotEvent::Error { err } if err.is_connection_error() => {
+                &mut self.latest_connection
+            }
+            LibrespotEvent::VolumeSet { .. } => &mut self.latest_volume,
+            _ => return,
+        };
+        *latest = Some(evt.clone());
+    }
+
+    fn replay(&self, categories: EventCategories) -> Vec<LibrespotEvent> {
+        [
+            &self.latest_connection,
+            &self.latest_track,
+            &self.latest_volume,
+        ]
+        .iter()
+        .filter_map(|evt| evt.as_ref())
+        .filter(|evt| categories & evt.category() != 0)
+        .cloned()
+        .collect()
+    }
+}
+
+impl LibrespotEventListener for ListenerRegistry {
+    fn notify(&self, evt: LibrespotEvent) {
+        let category = evt.category();
+        let subscriptions: Vec<_> = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.remember(&evt);
+            inner
+                .subscriptions
+                .iter()
+                .filter(|subscription| subscription.categories & category != 0)
+                .cloned()
+                .collect()
+        };
+
+        for subscription in subscriptions {
+            let _delivery = subscription.delivery.lock().unwrap();
+            if subscription.active.load(Ordering::SeqCst) {
+                subscription.listener.notify(evt.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::error::LibrespotError;
+    use crate::player::events::{CONNECTION_EVENTS, ERROR_EVENTS};
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<LibrespotEvent>>);
+
+    impl LibrespotEventListener for Recorder {
+        fn notify(&self, evt: LibrespotEvent) {
+            self.0.lock().unwrap().push(evt);
+        }
+    }
+
+    fn replayed(listeners: &ListenerRegistry, categories: EventCategories) -> Vec<LibrespotEvent> {
+        let recorder = Arc::new(Recorder::default());
+        listeners.subscribe(recorder.clone(), categories);
+        let events = recorder.0.lock().unwrap().clone();
+        events
+    }
+
+    #[test]
+    fn replays_connection_errors_to_error_listeners() {
+        let listeners = ListenerRegistry::default();
+        listeners.notify(LibrespotEvent::Connecting);
+        listeners.notify(LibrespotEvent::Error {
+            err: LibrespotError::BadCredentials,
+        });
+        listeners.notify(LibrespotEvent::Error {
+            err: LibrespotError::IllegalConfig("bitrate".to_string()),
+        });
+
+        assert!(matches!(
+            replayed(&listeners, ERROR_EVENTS)[..],
+            [LibrespotEvent::Error {
+                err: LibrespotError::BadCredentials
+            }]
+        ));
+        assert!(replayed(&listeners, CONNECTION_EVENTS).is_empty());
+
+        listeners.notify(LibrespotEvent::Connecting);
+        assert!(matches!(
+            replayed(&listeners, CONNECTION_EVENTS | ERROR_EVENTS)[..],
+            [LibrespotEvent::Connecting]
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+//! Login progress and failures
+//!
+//! `Session::connect` resolves the access point, connects to it and authenticates in one call
+//! without reporting progress. The first of these stages is reported before the call, the proxy
+//! bridge that all connections of librespot pass reports the others when it sees them start.
+
+use std::error::Error;
+use std::io;
+
+use librespot_core::session::SessionError;
+
+use crate::player::error::LibrespotError;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LoginStage {
+    /// Looking up an access point and its address
+    ResolvingAccessPoint = 0,
+    /// Connecting to the access point
+    ConnectingAccessPoint = 1,
+    /// The key exchange and sending the credentials
+    Authenticating = 2,
+    StartingSpirc = 3,
+    FetchingToken = 4,
+}
+
+/// Whether connecting again may fix the error
+#[must_use]
+pub fn is_retryable(err: &LibrespotError) -> bool {
+    !matches!(
+        err,
+        LibrespotError::BadCredentials
+            | LibrespotError::PremiumRequired
+            | LibrespotError::AccountRestricted(_)
+            | LibrespotError::MissingCredentials
+    )
+}
+
+/// Error for a failed `Session::connect`
+#[must_use]
+pub fn classify_session_error(err: &SessionError) -> LibrespotError {
+    match err {
+        SessionError::IoError(err) => classify_io_error(err),
+        SessionError::AuthenticationError(auth) => {
+            if let Some(err) = auth.source().and_then(|s| s.downcast_ref::<io::Error>()) {
+                return classify_io_error(err);
+            }
+
+            // the error type is private to librespot
+            let debug = format!("{:?}", auth);
+            classify_login_failure(login_failure_code(&debug), auth.to_string())
+        }
+    }
+}
+
+/// `ErrorCode` of a login failure from its debug output `LoginFailed(<code>)`
+fn login_failure_code(debug: &str) -> &str {
+    debug
+        .strip_prefix("LoginFailed(")
+        .and_then(|code| code.strip_suffix(')'))
+        .unwrap_or("")
+}
+
+/// Error for an `ErrorCode` of an `APLoginFailed` message
+fn classify_login_failure(code: &str, message: String) -> LibrespotError {
+    match code {
+        "BadCredentials" | "CouldNotValidateCredentials" => LibrespotError::BadCredentials,
+        "PremiumAccountRequired" => LibrespotError::PremiumRequired,
+        "TravelRestriction"
+        | "ExtraVerificationRequired"
+        | "ApplicationBanned"
+        | "AccountExists" => LibrespotError::AccountRestricted(message),
+        // the access point sheds load
+        "TryAnotherAP" => LibrespotError::RateLimited(message),
+        "BadConnectionId" => LibrespotError::AccessPointRefused(message),
+        _ => LibrespotError::Connection(message),
+    }
+}
+
+#[must_use]
+pub fn classify_io_error(err: &io::Error) -> LibrespotError {
+    const ENETDOWN: i32 = 100;
+    const ENETUNREACH: i32 = 101;
+    const EHOSTUNREACH: i32 = 113;
+
+    let message = err.to_string();
+    match err.kind() {
+        io::ErrorKind::TimedOut => LibrespotError::Timeout(message),
+        io::ErrorKind::ConnectionRefused
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::UnexpectedEof => LibrespotError::AccessPointRefused(message),
+        _ if matches!(
+            err.raw_os_error(),
+            Some(ENETDOWN) | Some(ENETUNREACH) | Some(EHOSTUNREACH)
+        ) =>
+        {
+            LibrespotError::NetworkUnreachable(message)
+        }
+        _ if is_lookup_error(err) => LibrespotError::NetworkUnreachable(message),
+        _ => LibrespotError::Connection(message),
+    }
+}
+
+/// Name resolution errors of tokio have no dedicated kind
+fn is_lookup_error(err: &io::Error) -> bool {
+    err.to_string().contains("lookup address")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_os_error(code: i32) -> LibrespotError {
+        classify_io_error(&io::Error::from_raw_os_error(code))
+    }
+
+    #[test]
+    fn parses_login_failure_code() {
+        assert_eq!(
+            login_failure_code("LoginFailed(BadCredentials)"),
+            "BadCredentials"
+        );
+        assert_eq!(
+            login_failure_code("LoginFailed(TryAnotherAP)"),
+            "TryAnotherAP"
+        );
+        assert_eq!(login_failure_code("LoginFailed"), "");
+        assert_eq!(login_failure_code("Packet(0, 1)"), "");
+    }
+
+    #[test]
+    fn classifies_login_failures() {
+        let classify = |code| classify_login_failure(code, "message".to_string());
+        assert!(matches!(
+            classify("BadCredentials"),
+            LibrespotError::BadCredentials
+        ));
+        assert!(matches!(
+            classify("CouldNotValidateCredentials"),
+            LibrespotError::BadCredentials
+        ));
+        assert!(matches!(
+            classify("PremiumAccountRequired"),
+            LibrespotError::PremiumRequired
+        ));
+        assert!(matches!(
+            classify("TravelRestriction"),
+            LibrespotError::AccountRestricted(_)
+        ));
+        assert!(matches!(
+            classify("TryAnotherAP"),
+            LibrespotError::RateLimited(_)
+        ));
+        assert!(matches!(
+            classify("BadConnectionId"),
+            LibrespotError::AccessPointRefused(_)
+        ));
+        assert!(matches!(classify(""), LibrespotError::Connection(_)));
+    }
+
+    #[test]
+    fn classifies_errno() {
+        // ENETDOWN, ENETUNREACH, EHOSTUNREACH
+        for code in &[100, 101, 113] {
+            assert!(matches!(
+                raw_os_error(*code),
+                LibrespotError::NetworkUnreachable(_)
+            ));
+        }
+        // ECONNREFUSED, ECONNRESET
+        for code in &[111, 104] {
+            assert!(matches!(
+                raw_os_error(*code),
+                LibrespotError::AccessPointRefused(_)
+            ));
+        }
+        // ETIMEDOUT
+        assert!(matches!(raw_os_error(110), LibrespotError::Timeout(_)));
+        // EACCES
+        assert!(matches!(raw_os_error(13), LibrespotError::Connection(_)));
+    }
+
+    #[test]
+    fn classifies_lookup_errors() {
+        let err = io::Error::new(
+            io::ErrorKind::Other,
+            "failed to lookup address information: Name or service not known",
+        );
+        assert!(matches!(
+            classify_io_error(&err),
+            LibrespotError::NetworkUnreachable(_)
+        ));
+    }
+}
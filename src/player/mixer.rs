@@ -0,0 +1,101 @@
+//! Mixer that can be replaced while playing
+//!
+//! Spirc and player keep the mixer and its audio filter for their whole life. [`LiveMixer`] hands
+//! them a stable front and swaps the mixer behind it, so that the mixer settings and the volume
+//! control apply without a new player.
+
+use std::sync::{Arc, Mutex};
+
+use librespot_playback::mixer::{self, AudioFilter, Mixer, MixerConfig, MixerFn};
+use log::info;
+
+type SharedFilter = Arc<Mutex<Option<Box<dyn AudioFilter + Send>>>>;
+
+#[derive(Clone)]
+pub struct LiveMixer {
+    mixer: Arc<Mutex<Box<dyn Mixer>>>,
+    filter: SharedFilter,
+}
+
+impl LiveMixer {
+    #[must_use]
+    pub fn new(mixer_fn: MixerFn, config: MixerConfig) -> Self {
+        let mixer = mixer_fn(config);
+        Self {
+            filter: Arc::new(Mutex::new(mixer.get_audio_filter())),
+            mixer: Arc::new(Mutex::new(mixer)),
+        }
+    }
+
+    /// Replace the mixer, keeping the current volume
+    pub fn replace(&self, mixer_fn: MixerFn, config: MixerConfig) {
+        info!("Replacing mixer ...");
+        let mut mixer = self.mixer.lock().unwrap();
+        let volume = mixer.volume();
+        let new_mixer = mixer_fn(config);
+        new_mixer.set_volume(volume);
+        *self.filter.lock().unwrap() = new_mixer.get_audio_filter();
+        *mixer = new_mixer;
+    }
+}
+
+impl Mixer for LiveMixer {
+    fn open(config: MixerConfig) -> Self {
+        let mixer_fn = mixer::find(None).expect("default mixer");
+        Self::new(mixer_fn, config)
+    }
+
+    fn set_volume(&self, volume: u16) {
+        self.mixer.lock().unwrap().set_volume(volume);
+    }
+
+    fn volume(&self) -> u16 {
+        self.mixer.lock().unwrap().volume()
+    }
+
+    fn get_audio_filter(&self) -> Option<Box<dyn AudioFilter + Send>> {
+        Some(Box::new(LiveFilter(self.filter.clone())))
+    }
+}
+
+/// Applies the audio filter of the current mixer
+struct LiveFilter(SharedFilter);
+
+impl AudioFilter for LiveFilter {
+    fn modify_stream(&self, data: &mut [f64]) {
+        if let Some(filter) = &*self.0.lock().unwrap() {
+            filter.modify_stream(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use librespot_playback::config::VolumeCtrl;
+
+    fn config(volume_ctrl: VolumeCtrl) -> MixerConfig {
+        MixerConfig {
+            volume_ctrl,
+            ..MixerConfig::default()
+        }
+    }
+
+    #[test]
+    fn replaced_mixer_keeps_volume_and_filters_with_new_control() {
+        let softvol = mixer::find(None).unwrap();
+        let mixer = LiveMixer::new(softvol, config(VolumeCtrl::Log(60.0)));
+        let filter = mixer.get_audio_filter().unwrap();
+        mixer.set_volume(0x8000);
+
+        let mut data = [1.0];
+        filter.modify_stream(&mut data);
+        assert!(data[0] < 0.1);
+
+        mixer.replace(softvol, config(VolumeCtrl::Linear));
+        assert!((i32::from(mixer.volume()) - 0x8000).abs() <= 1);
+        let mut data = [1.0];
+        filter.modify_stream(&mut data);
+        assert!((data[0] - 0.5).abs() < 0.01);
+    }
+}
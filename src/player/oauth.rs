@@ -0,0 +1,485 @@
+//! OAuth login
+//!
+//! Authorization code flow with PKCE: the user logs in on the website of Spotify, which redirects
+//! the browser to a small listener on localhost. The code from the redirect is exchanged for an
+//! access token, which authenticates the session instead of a password. The session then stores
+//! reusable credentials in the cache like after a password login.
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use librespot_core::authentication::Credentials;
+use librespot_protocol::authentication::AuthenticationType;
+use log::{info, warn};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::player::error::{LibrespotError, LibrespotResult};
+use crate::player::secret::{zeroize, SecretCredentials, SecretString};
+use crate::player::{CLIENT_ID, SCOPES};
+
+pub const AUTHORIZE_URL: &str = "https://accounts.spotify.com/authorize";
+pub const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+pub const DEFAULT_REDIRECT_PORT: u16 = 8898;
+
+const REDIRECT_PATH: &str = "/login";
+/// Time the user has to log in
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time a browser has to send the request line of the redirect
+const REDIRECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time a cancelled login has to release the redirect port
+const BIND_TIMEOUT: Duration = Duration::from_secs(1);
+
+const RESPONSE_PAGE: &str = "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+<meta name=\"viewport\" content=\"width=device-width\"><title>Sailify</title></head>\
+<body><p>{}</p></body></html>";
+
+#[derive(Clone, Debug)]
+pub struct OAuthConfig {
+    pub client_id: String,
+    /// Comma separated scopes
+    pub scopes: String,
+    pub authorize_url: String,
+    pub token_url: String,
+    /// Port of the redirect listener on localhost, 0 to pick a free one
+    pub redirect_port: u16,
+    /// Proxy URL for the token request
+    pub proxy: Option<String>,
+}
+
+impl Default for OAuthConfig {
+    fn default() -> Self {
+        Self {
+            client_id: CLIENT_ID.to_string(),
+            scopes: SCOPES.to_string(),
+            authorize_url: AUTHORIZE_URL.to_string(),
+            token_url: TOKEN_URL.to_string(),
+            redirect_port: DEFAULT_REDIRECT_PORT,
+            proxy: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OAuthToken {
+    pub access_token: SecretString,
+    pub token_type: String,
+    pub expires_in: u32,
+    #[serde(default)]
+    pub refresh_token: Option<SecretString>,
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+impl OAuthToken {
+    /// Credentials to authenticate a session with
+    #[must_use]
+    pub fn credentials(&self) -> SecretCredentials {
+        SecretCredentials::new(Credentials {
+            username: String::new(),
+            auth_type: AuthenticationType::AUTHENTICATION_SPOTIFY_TOKEN,
+            auth_data: self.access_token.expose().as_bytes().to_vec(),
+        })
+    }
+}
+
+/// A login waiting for the redirect
+pub struct OAuthLogin {
+    config: OAuthConfig,
+    listener: TcpListener,
+    redirect_uri: String,
+    code_verifier: String,
+    state: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl OAuthLogin {
+    /// Start listening for the redirect
+    ///
+    /// A cancelled login may still hold the port for a moment, binding is retried until it is
+    /// released.
+    pub fn new(config: OAuthConfig) -> LibrespotResult<Self> {
+        let listener = bind_redirect_port(config.redirect_port)?;
+        listener.set_nonblocking(true)?;
+        let redirect_uri = format!(
+            "http://127.0.0.1:{}{}",
+            listener.local_addr()?.port(),
+            REDIRECT_PATH
+        );
+
+        Ok(Self {
+            config,
+            listener,
+            redirect_uri,
+            code_verifier: random_string(64),
+            state: random_string(16),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    #[must_use]
+    pub fn redirect_uri(&self) -> &str {
+        &self.redirect_uri
+    }
+
+    /// URL the user has to open in a browser
+    pub fn authorize_url(&self) -> LibrespotResult<Url> {
+        let mut url = Url::parse(&self.config.authorize_url).map_err(|err| {
+            LibrespotError::IllegalConfig(format!("Invalid authorize URL: {}", err))
+        })?;
+        url.query_pairs_mut()
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("response_type", "code")
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("code_challenge_method", "S256")
+            .append_pair("code_challenge", &code_challenge(&self.code_verifier))
+            .append_pair("scope", &self.config.scopes.replace(',', " "))
+            .append_pair("state", &self.state);
+        Ok(url)
+    }
+
+    /// Flag that aborts [`OAuthLogin::wait_for_code`] when set
+    #[must_use]
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    /// Wait for the redirect and return the authorization code
+    pub fn wait_for_code(&self) -> LibrespotResult<String> {
+        let deadline = Instant::now() + LOGIN_TIMEOUT;
+        loop {
+            if self.cancelled.load(Ordering::SeqCst) {
+                return Err(LibrespotError::OAuth("Login was cancelled".to_string()));
+            }
+
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Some(result) = self.handle_redirect(stream) {
+                        return result;
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        return Err(LibrespotError::OAuth("Login timed out".to_string()));
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Answer a request to the listener
+    ///
+    /// Returns `None` for requests that are not the redirect, like the favicon. A broken
+    /// connection is only logged, it must not end the login.
+    fn handle_redirect(&self, mut stream: TcpStream) -> Option<LibrespotResult<String>> {
+        let request_line = match read_request_line(&stream) {
+            Ok(request_line) => request_line,
+            Err(err) => {
+                warn!("Ignoring broken OAuth redirect request: {}", err);
+                return None;
+            }
+        };
+
+        let target = match request_line.split(' ').collect::<Vec<_>>()[..] {
+            ["GET", target, _] => target,
+            _ => {
+                write_response(&mut stream, "400 Bad Request", "Bad request");
+                return None;
+            }
+        };
+        let url = Url::parse("http://127.0.0.1")
+            .and_then(|base| base.join(target))
+            .ok()
+            .filter(|url| url.path() == REDIRECT_PATH);
+        let url = match url {
+            Some(url) => url,
+            None => {
+                write_response(&mut stream, "404 Not Found", "Not found");
+                return None;
+            }
+        };
+
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        if param("state").as_deref() != Some(&self.state) {
+            warn!("Ignoring OAuth redirect with unknown state");
+            write_response(&mut stream, "400 Bad Request", "Unknown login request");
+            return None;
+        }
+
+        let result = match (param("code"), param("error")) {
+            (Some(code), _) => Ok(code),
+            (None, error) => Err(LibrespotError::OAuth(format!(
+                "Authorization failed: {}",
+                error.as_deref().unwrap_or("no code")
+            ))),
+        };
+        let message = if result.is_ok() {
+            "Logged in. You can return to Sailify now."
+        } else {
+            "Login failed. Please return to Sailify and try again."
+        };
+        write_response(&mut stream, "200 OK", message);
+        Some(result)
+    }
+
+    /// Exchange the authorization code for a token
+    pub fn exchange_code(&self, code: &str) -> LibrespotResult<OAuthToken> {
+        let mut agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT);
+        if let Some(proxy) = self.config.proxy.as_deref().filter(|p| !p.is_empty()) {
+            let proxy = ureq::Proxy::new(proxy).map_err(|err| {
+                LibrespotError::IllegalConfig(format!("Invalid proxy URL: {}", err))
+            })?;
+            agent = agent.proxy(proxy);
+        }
+
+        let response = agent
+            .build()
+            .post(&self.config.token_url)
+            .send_form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.redirect_uri),
+                ("client_id", &self.config.client_id),
+                ("code_verifier", &self.code_verifier),
+            ])
+            .map_err(|err| match err {
+                ureq::Error::Status(429, _) => LibrespotError::RateLimited(err.to_string()),
+                _ => LibrespotError::OAuth(format!("Token request failed: {}", err)),
+            })?;
+
+        // read the body ourselves, so that the tokens in it can be wiped, sized so that it is not
+        // reallocated leaving copies behind
+        let capacity = response
+            .header("Content-Length")
+            .and_then(|len| len.parse::<usize>().ok())
+            .map_or(4096, |len| len.min(64 * 1024));
+        let mut body = Vec::with_capacity(capacity);
+        let result = response
+            .into_reader()
+            .read_to_end(&mut body)
+            .map_err(|err| LibrespotError::OAuth(format!("Token request failed: {}", err)))
+            .and_then(|_| {
+                serde_json::from_slice::<OAuthToken>(&body).map_err(|err| {
+                    LibrespotError::OAuth(format!("Invalid token response: {}", err))
+                })
+            });
+        zeroize(&mut body);
+        result
+    }
+
+    /// Wait for the redirect and exchange the code
+    pub fn complete(self) -> LibrespotResult<OAuthToken> {
+        let code = self.wait_for_code()?;
+        info!("Received OAuth authorization code");
+        self.exchange_code(&code)
+    }
+}
+
+fn bind_redirect_port(port: u16) -> LibrespotResult<TcpListener> {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let deadline = Instant::now() + BIND_TIMEOUT;
+    loop {
+        match TcpListener::bind(addr) {
+            Ok(listener) => return Ok(listener),
+            Err(err) if err.kind() == ErrorKind::AddrInUse => {
+                if Instant::now() >= deadline {
+                    return Err(LibrespotError::OAuth(format!(
+                        "Port {} for the login redirect is in use",
+                        port
+                    )));
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+fn read_request_line(stream: &TcpStream) -> io::Result<String> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REDIRECT_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(stream).read_line(&mut request_line)?;
+    Ok(request_line)
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// S256 challenge of a PKCE code verifier
+#[must_use]
+pub fn code_challenge(code_verifier: &str) -> String {
+    base64::encode_config(
+        Sha256::digest(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, message: &str) {
+    let body = RESPONSE_PAGE.replace("{}", message);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\n\
+        Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    if let Err(err) = stream.write_all(response.as_bytes()) {
+        warn!("Failed to answer OAuth redirect: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(token_url: String) -> OAuthConfig {
+        OAuthConfig {
+            client_id: "client".to_string(),
+            token_url,
+            redirect_port: 0,
+            ..OAuthConfig::default()
+        }
+    }
+
+    /// Answers one token request with `response`, returns the request body
+    fn token_endpoint(response: &'static str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let url = format!("http://{}/api/token", listener.local_addr().unwrap());
+        let thread = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                Connection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+        (url, thread)
+    }
+
+    fn redirect(login: &OAuthLogin, query: &str) -> TcpStream {
+        let addr = login.listener.local_addr().unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {}?{} HTTP/1.1\r\n\r\n", REDIRECT_PATH, query).unwrap();
+        stream
+    }
+
+    #[test]
+    fn exchanges_code_at_token_endpoint() {
+        let (url, endpoint) = token_endpoint(
+            r#"{"access_token":"access","token_type":"Bearer","expires_in":3600,"refresh_token":"refresh"}"#,
+        );
+        let login = OAuthLogin::new(config(url)).unwrap();
+
+        let token = login.exchange_code("the-code").unwrap();
+        assert_eq!(token.access_token.expose(), "access");
+        assert_eq!(token.expires_in, 3600);
+        assert_eq!(
+            token.refresh_token.as_ref().map(SecretString::expose),
+            Some("refresh")
+        );
+        assert_eq!(token.credentials().expose().auth_data, b"access");
+
+        let body = endpoint.join().unwrap();
+        let form: Vec<(String, String)> = url::form_urlencoded::parse(body.as_bytes())
+            .into_owned()
+            .collect();
+        let param = |name: &str| {
+            form.iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(param("grant_type"), Some("authorization_code"));
+        assert_eq!(param("code"), Some("the-code"));
+        assert_eq!(param("client_id"), Some("client"));
+        assert_eq!(param("code_verifier"), Some(login.code_verifier.as_str()));
+        assert_eq!(param("redirect_uri"), Some(login.redirect_uri()));
+    }
+
+    #[test]
+    fn reports_invalid_token_response() {
+        let (url, endpoint) = token_endpoint(r#"{"error":"invalid_grant"}"#);
+        let login = OAuthLogin::new(config(url)).unwrap();
+
+        assert!(matches!(
+            login.exchange_code("the-code"),
+            Err(LibrespotError::OAuth(_))
+        ));
+        endpoint.join().unwrap();
+    }
+
+    #[test]
+    fn waits_for_redirect_despite_broken_requests() {
+        let login = OAuthLogin::new(config(TOKEN_URL.to_string())).unwrap();
+        let addr = login.listener.local_addr().unwrap();
+
+        // closed without a request, then unrelated and forged requests
+        drop(TcpStream::connect(addr).unwrap());
+        let _favicon = {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET /favicon.ico HTTP/1.1\r\n\r\n").unwrap();
+            stream
+        };
+        let _forged = redirect(&login, "code=forged&state=unknown");
+        let _redirect = redirect(&login, &format!("code=the-code&state={}", login.state));
+
+        assert_eq!(login.wait_for_code().unwrap(), "the-code");
+    }
+
+    #[test]
+    fn rebinds_port_released_after_cancel() {
+        let login = OAuthLogin::new(config(TOKEN_URL.to_string())).unwrap();
+        let port = login.listener.local_addr().unwrap().port();
+        let cancelled = login.cancel_flag();
+        let waiting = thread::spawn(move || login.wait_for_code());
+
+        cancelled.store(true, Ordering::SeqCst);
+        let login = OAuthLogin::new(OAuthConfig {
+            redirect_port: port,
+            ..config(TOKEN_URL.to_string())
+        })
+        .unwrap();
+        assert_eq!(login.listener.local_addr().unwrap().port(), port);
+        assert!(waiting.join().unwrap().is_err());
+    }
+}
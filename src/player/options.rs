@@ -1,22 +1,34 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::{APPLICATION_NAME, ORGANIZATION_NAME};
+use crate::player::credentials::CredentialStore;
+use crate::player::error::LibrespotResult;
+use crate::player::profiles;
+use crate::player::proxy;
+use crate::player::reconnect::ReconnectPolicy;
+use crate::player::secret::{SecretCredentials, SecretString};
+use crate::player::settings::{self, Settings, SETTINGS_FILE};
 use librespot_playback::config::{AudioFormat, Bitrate, VolumeCtrl};
+use log::warn;
 use os_release::OsRelease;
 use uuid::Uuid;
 
-use crate::utils::xdg_base_dirs;
+const DEVICE_ID_FILE: &str = "device_id";
 
 #[derive(Clone)]
 pub struct Options {
+    pub profile: String,
+    pub settings_file: PathBuf,
     pub system_cache: Option<PathBuf>,
     pub audio_cache: Option<PathBuf>,
     pub device_name: String,
     pub device_id: String,
     pub bitrate: Bitrate,
     pub username: Option<String>,
-    pub password: Option<String>,
+    pub password: Option<SecretString>,
+    /// Token credentials of an OAuth login, preferred over username and password
+    pub credentials: Option<SecretCredentials>,
+    /// Proxy URL including the credentials from the credential store
     pub proxy: Option<String>,
     pub ap_port: Option<u16>,
     pub format: AudioFormat,
@@ -33,39 +45,325 @@ pub struct Options {
     pub autoplay: bool,
     pub gapless: bool,
     pub cache_size_limit: Option<u64>,
+    pub reconnect_policy: ReconnectPolicy,
+    /// Let Connect apps on the local network hand over a login
+    pub discovery: bool,
+}
+
+/// A setting that can be changed while the player runs
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Setting {
+    DeviceName,
+    Bitrate,
+    Format,
+    Backend,
+    BackendDevice,
+    Mixer,
+    MixerName,
+    MixerCard,
+    MixerIndex,
+    VolumeCtrl,
+    InitialVolume,
+    VolumeNormalisation,
+    NormalisationPregain,
+    Autoplay,
+    Gapless,
+    ReconnectPolicy,
+    Proxy,
+    ApPort,
+    CacheSizeLimit,
+    Discovery,
+}
+
+impl Setting {
+    /// Name of the setting in the settings file
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Setting::DeviceName => "device_name",
+            Setting::Bitrate => "bitrate",
+            Setting::Format => "format",
+            Setting::Backend => "backend",
+            Setting::BackendDevice => "backend_device",
+            Setting::Mixer => "mixer",
+            Setting::MixerName => "mixer_name",
+            Setting::MixerCard => "mixer_card",
+            Setting::MixerIndex => "mixer_index",
+            Setting::VolumeCtrl => "volume_ctrl",
+            Setting::InitialVolume => "initial_volume",
+            Setting::VolumeNormalisation => "volume_normalisation",
+            Setting::NormalisationPregain => "normalisation_pregain",
+            Setting::Autoplay => "autoplay",
+            Setting::Gapless => "gapless",
+            Setting::ReconnectPolicy => "reconnect_policy",
+            Setting::Proxy => "proxy",
+            Setting::ApPort => "ap_port",
+            Setting::CacheSizeLimit => "cache_size_limit",
+            Setting::Discovery => "discovery",
+        }
+    }
+}
+
+/// Settings that differ between two sets of options
+#[derive(Clone, Debug, Default)]
+pub struct SettingsChanges {
+    /// Settings that are applied to the running player and Spirc
+    ///
+    /// The mixer is replaced for the mixer settings, the initial volume is only used for the next
+    /// start.
+    pub live: Vec<Setting>,
+    /// Settings that are applied with a new player and Spirc
+    ///
+    /// librespot only takes them when creating these. Playback continues where it was.
+    pub rebuild: Vec<Setting>,
+    /// Settings that need a restart of the runtime
+    pub restart: Vec<Setting>,
+}
+
+impl SettingsChanges {
+    /// Whether applying the settings needs a new player and Spirc
+    #[must_use]
+    pub fn needs_rebuild(&self) -> bool {
+        !self.rebuild.is_empty()
+    }
+
+    /// Whether the mixer has to be replaced
+    #[must_use]
+    pub fn needs_new_mixer(&self) -> bool {
+        self.live.iter().any(|setting| {
+            matches!(
+                setting,
+                Setting::Mixer
+                    | Setting::MixerName
+                    | Setting::MixerCard
+                    | Setting::MixerIndex
+                    | Setting::VolumeCtrl
+            )
+        })
+    }
+
+    /// Settings that take effect without a restart
+    #[must_use]
+    pub fn applied(&self) -> Vec<Setting> {
+        self.live.iter().chain(&self.rebuild).copied().collect()
+    }
 }
 
 impl Options {
-    pub fn read_from_fs() -> Self {
-        let hw_name = OsRelease::new_from("/etc/hw-release")
-            .map_or_else(|_| "Sailfish OS".to_string(), |hw| hw.name);
-        let config_dir = xdg_base_dirs::config_home()
-            .join(ORGANIZATION_NAME)
-            .join(APPLICATION_NAME);
-        let cache_dir = xdg_base_dirs::cache_home()
-            .join(ORGANIZATION_NAME)
-            .join(APPLICATION_NAME);
-
-        let device_id_path = config_dir.join("device_id");
-        let device_id = if let Ok(device_id) = fs::read_to_string(&device_id_path) {
-            device_id
-        } else {
-            let mut buffer = Uuid::encode_buffer();
-            let device_id = Uuid::new_v4().to_simple().encode_lower(&mut buffer);
-
-            fs::create_dir_all(&config_dir).unwrap();
-            fs::write(&device_id_path, &device_id).unwrap();
-            (*device_id).to_string()
-        };
+    /// Read the options of a profile with the proxy credentials from `store`
+    pub fn read_profile(profile: &str, store: &dyn CredentialStore) -> Self {
+        let dirs = profiles::profile_dirs(profile);
+        let device_id = read_device_id(&dirs.config);
+        let settings_file = dirs.config.join(SETTINGS_FILE);
+        let settings = Settings::read_from(&settings_file);
+        let user_info = store.load_proxy_user_info().unwrap_or_else(|err| {
+            warn!("Failed to load proxy credentials: {}", err);
+            None
+        });
+        let proxy = settings.proxy.as_deref().map(|url| match &user_info {
+            Some(user_info) => proxy::with_user_info(url, user_info),
+            None => url.to_string(),
+        });
 
         Self {
-            audio_cache: Some(cache_dir.join("files")),
-            system_cache: Some(config_dir),
-            device_name: hw_name,
+            profile: profile.to_string(),
+            settings_file,
+            audio_cache: Some(dirs.cache.join("files")),
+            system_cache: Some(dirs.config),
+            device_name: settings
+                .device_name
+                .clone()
+                .unwrap_or_else(default_device_name),
             device_id,
+            bitrate: settings.bitrate(),
+            username: None,
+            password: None,
+            credentials: None,
+            proxy,
+            ap_port: settings.ap_port,
+            format: settings.format(),
+            backend: settings.backend.clone(),
+            backend_device: settings.backend_device.clone(),
+            mixer: settings.mixer.clone(),
+            volume_ctrl: settings.volume_ctrl(),
+            reconnect_policy: settings.reconnect_policy(),
+            mixer_name: settings.mixer_name,
+            mixer_card: settings.mixer_card,
+            mixer_index: settings.mixer_index,
+            initial_volume: settings.initial_volume,
+            volume_normalisation: settings.volume_normalisation,
+            normalisation_pregain: settings.normalisation_pregain,
+            autoplay: settings.autoplay,
+            gapless: settings.gapless,
+            cache_size_limit: settings.cache_size_limit,
+            discovery: settings.discovery,
+        }
+    }
+
+    /// Write the settings and keep the proxy credentials in `store`
+    pub fn save_to_fs(&self, store: &dyn CredentialStore) -> LibrespotResult<()> {
+        let user_info = self
+            .proxy
+            .as_deref()
+            .and_then(|url| proxy::split_user_info(url).1);
+        store.save_proxy_user_info(user_info.as_ref())?;
+        self.to_settings().write_to(&self.settings_file)
+    }
+
+    /// Settings of `self` that differ from the `running` options
+    #[must_use]
+    pub fn changed_settings(&self, running: &Options) -> SettingsChanges {
+        let mut changes = SettingsChanges::default();
+        let mut live = |setting, changed| {
+            if changed {
+                changes.live.push(setting);
+            }
+        };
+        live(Setting::Mixer, self.mixer != running.mixer);
+        live(Setting::MixerName, self.mixer_name != running.mixer_name);
+        live(Setting::MixerCard, self.mixer_card != running.mixer_card);
+        live(Setting::MixerIndex, self.mixer_index != running.mixer_index);
+        let volume_ctrl_changed = settings::volume_ctrl_to_str(self.volume_ctrl)
+            != settings::volume_ctrl_to_str(running.volume_ctrl)
+            || settings::volume_ctrl_range(self.volume_ctrl)
+                != settings::volume_ctrl_range(running.volume_ctrl);
+        // Connect apps only learn from a new Spirc whether the volume can be changed
+        let fixed_changed = matches!(self.volume_ctrl, VolumeCtrl::Fixed)
+            != matches!(running.volume_ctrl, VolumeCtrl::Fixed);
+        live(Setting::VolumeCtrl, volume_ctrl_changed && !fixed_changed);
+        live(
+            Setting::InitialVolume,
+            self.initial_volume != running.initial_volume,
+        );
+        live(
+            Setting::ReconnectPolicy,
+            self.reconnect_policy != running.reconnect_policy,
+        );
+
+        let mut rebuild = |setting, changed| {
+            if changed {
+                changes.rebuild.push(setting);
+            }
+        };
+        rebuild(Setting::DeviceName, self.device_name != running.device_name);
+        rebuild(Setting::Bitrate, self.bitrate != running.bitrate);
+        rebuild(Setting::Format, self.format != running.format);
+        rebuild(Setting::Backend, self.backend != running.backend);
+        rebuild(
+            Setting::BackendDevice,
+            self.backend_device != running.backend_device,
+        );
+        rebuild(Setting::VolumeCtrl, fixed_changed);
+        rebuild(
+            Setting::VolumeNormalisation,
+            self.volume_normalisation != running.volume_normalisation,
+        );
+        rebuild(
+            Setting::NormalisationPregain,
+            self.normalisation_pregain != running.normalisation_pregain,
+        );
+        rebuild(Setting::Autoplay, self.autoplay != running.autoplay);
+        rebuild(Setting::Gapless, self.gapless != running.gapless);
+
+        let mut restart = |setting, changed| {
+            if changed {
+                changes.restart.push(setting);
+            }
+        };
+        restart(Setting::Proxy, self.proxy != running.proxy);
+        restart(Setting::ApPort, self.ap_port != running.ap_port);
+        restart(
+            Setting::CacheSizeLimit,
+            self.cache_size_limit != running.cache_size_limit,
+        );
+        restart(Setting::Discovery, self.discovery != running.discovery);
+
+        changes
+    }
+
+    /// Options of a running session after the live settings of `self` were applied
+    #[must_use]
+    pub fn applied_to(&self, running: &Options) -> Options {
+        Options {
+            proxy: running.proxy.clone(),
+            ap_port: running.ap_port,
+            cache_size_limit: running.cache_size_limit,
+            discovery: running.discovery,
+            ..self.clone()
+        }
+    }
+
+    fn to_settings(&self) -> Settings {
+        Settings {
+            device_name: Some(self.device_name.clone())
+                .filter(|name| *name != default_device_name()),
+            bitrate: settings::bitrate_to_str(self.bitrate).to_string(),
+            format: settings::audio_format_to_str(self.format).to_string(),
+            backend: self.backend.clone(),
+            backend_device: self.backend_device.clone(),
+            mixer: self.mixer.clone(),
+            mixer_name: self.mixer_name.clone(),
+            mixer_card: self.mixer_card.clone(),
+            mixer_index: self.mixer_index,
+            initial_volume: self.initial_volume,
+            volume_normalisation: self.volume_normalisation,
+            normalisation_pregain: self.normalisation_pregain,
+            volume_ctrl: settings::volume_ctrl_to_str(self.volume_ctrl).to_string(),
+            volume_range: settings::volume_ctrl_range(self.volume_ctrl),
+            autoplay: self.autoplay,
+            gapless: self.gapless,
+            cache_size_limit: self.cache_size_limit,
+            ap_port: self.ap_port,
+            proxy: self.proxy.clone(),
+            reconnect_max_attempts: self.reconnect_policy.max_attempts,
+            reconnect_initial_delay_ms: self.reconnect_policy.initial_delay.as_millis() as u64,
+            reconnect_max_delay_ms: self.reconnect_policy.max_delay.as_millis() as u64,
+            discovery: self.discovery,
+            ..Settings::default()
+        }
+    }
+}
+
+/// Device id of a profile, created on first use
+///
+/// Every profile is a device of its own for Spotify. The default profile keeps the id of
+/// installations from before profiles existed.
+fn read_device_id(config_dir: &Path) -> String {
+    let device_id_path = config_dir.join(DEVICE_ID_FILE);
+    if let Ok(device_id) = fs::read_to_string(&device_id_path) {
+        return device_id;
+    }
+
+    let mut buffer = Uuid::encode_buffer();
+    let device_id = Uuid::new_v4().to_simple().encode_lower(&mut buffer);
+    let result =
+        fs::create_dir_all(config_dir).and_then(|()| fs::write(&device_id_path, &device_id));
+    if let Err(err) = result {
+        warn!("Failed to store device id: {}", err);
+    }
+    (*device_id).to_string()
+}
+
+fn default_device_name() -> String {
+    OsRelease::new_from("/etc/hw-release").map_or_else(|_| "Sailfish OS".to_string(), |hw| hw.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> Options {
+        Options {
+            profile: profiles::DEFAULT_PROFILE.to_string(),
+            settings_file: PathBuf::from(SETTINGS_FILE),
+            system_cache: None,
+            audio_cache: None,
+            device_name: "Sailfish OS".to_string(),
+            device_id: "device".to_string(),
             bitrate: Bitrate::default(),
             username: None,
             password: None,
+            credentials: None,
             proxy: None,
             ap_port: None,
             format: AudioFormat::default(),
@@ -78,10 +376,69 @@ impl Options {
             initial_volume: None,
             volume_normalisation: false,
             normalisation_pregain: None,
-            volume_ctrl: VolumeCtrl::default(),
+            volume_ctrl: VolumeCtrl::Log(60.0),
             autoplay: false,
             gapless: true,
-            cache_size_limit: Some(2 * 1024 * 1024 * 1024),
+            cache_size_limit: None,
+            reconnect_policy: ReconnectPolicy::default(),
+            discovery: false,
         }
     }
+
+    #[test]
+    fn applies_mixer_settings_live() {
+        let running = options();
+        let changed = Options {
+            volume_ctrl: VolumeCtrl::Log(40.0),
+            mixer_index: 1,
+            initial_volume: Some(50),
+            ..options()
+        };
+
+        let changes = changed.changed_settings(&running);
+        assert_eq!(
+            changes.live,
+            [
+                Setting::MixerIndex,
+                Setting::VolumeCtrl,
+                Setting::InitialVolume
+            ]
+        );
+        assert!(changes.needs_new_mixer());
+        assert!(!changes.needs_rebuild());
+        assert!(changes.restart.is_empty());
+    }
+
+    #[test]
+    fn rebuilds_for_player_and_spirc_settings() {
+        let running = options();
+        let changed = Options {
+            device_name: "Phone".to_string(),
+            autoplay: true,
+            volume_ctrl: VolumeCtrl::Fixed,
+            proxy: Some("socks5://proxy".to_string()),
+            ..options()
+        };
+
+        let changes = changed.changed_settings(&running);
+        assert!(changes.live.is_empty());
+        assert_eq!(
+            changes.rebuild,
+            [Setting::DeviceName, Setting::VolumeCtrl, Setting::Autoplay]
+        );
+        assert_eq!(changes.restart, [Setting::Proxy]);
+        assert_eq!(changes.applied(), changes.rebuild);
+    }
+
+    #[test]
+    fn keeps_device_id_per_profile() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let profile_a = dir.path().join("a");
+        let profile_b = dir.path().join("b");
+
+        let device_id = read_device_id(&profile_a);
+        assert_eq!(device_id.len(), 32);
+        assert_eq!(read_device_id(&profile_a), device_id);
+        assert_ne!(read_device_id(&profile_b), device_id);
+    }
 }
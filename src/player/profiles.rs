@@ -0,0 +1,243 @@
+//! Account profiles
+//!
+//! Every profile has its own settings, credentials, device id and caches. The default profile uses
+//! the config and cache directories of the application, so installations from before profiles
+//! existed keep their login. Other profiles live in a `profiles` subdirectory of both.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use log::{info, warn};
+
+use crate::player::error::{LibrespotError, LibrespotResult};
+use crate::utils::xdg_base_dirs;
+use crate::{APPLICATION_NAME, ORGANIZATION_NAME};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+const PROFILES_DIR: &str = "profiles";
+const ACTIVE_PROFILE_FILE: &str = "active_profile";
+const MAX_NAME_LEN: usize = 64;
+
+/// Directories of a profile
+pub struct ProfileDirs {
+    /// Settings and credentials
+    pub config: PathBuf,
+    /// Audio files
+    pub cache: PathBuf,
+}
+
+#[must_use]
+pub fn config_dir() -> PathBuf {
+    xdg_base_dirs::config_home()
+        .join(ORGANIZATION_NAME)
+        .join(APPLICATION_NAME)
+}
+
+#[must_use]
+pub fn cache_dir() -> PathBuf {
+    xdg_base_dirs::cache_home()
+        .join(ORGANIZATION_NAME)
+        .join(APPLICATION_NAME)
+}
+
+#[must_use]
+pub fn profile_dirs(name: &str) -> ProfileDirs {
+    if name == DEFAULT_PROFILE {
+        ProfileDirs {
+            config: config_dir(),
+            cache: cache_dir(),
+        }
+    } else {
+        ProfileDirs {
+            config: config_dir().join(PROFILES_DIR).join(name),
+            cache: cache_dir().join(PROFILES_DIR).join(name),
+        }
+    }
+}
+
+/// Names of all profiles, the default profile first
+#[must_use]
+pub fn list() -> Vec<String> {
+    let mut names = match fs::read_dir(config_dir().join(PROFILES_DIR)) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| name != DEFAULT_PROFILE && validate_name(name).is_ok())
+            .collect(),
+        Err(err) => {
+            if err.kind() != io::ErrorKind::NotFound {
+                warn!("Failed to list profiles: {}", err);
+            }
+            Vec::new()
+        }
+    };
+    names.sort();
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    names
+}
+
+#[must_use]
+pub fn exists(name: &str) -> bool {
+    name == DEFAULT_PROFILE || (validate_name(name).is_ok() && profile_dirs(name).config.is_dir())
+}
+
+/// Profile that was active last time, the default profile if it is gone
+#[must_use]
+pub fn active() -> String {
+    match fs::read_to_string(config_dir().join(ACTIVE_PROFILE_FILE)) {
+        Ok(name) if exists(name.trim()) => name.trim().to_string(),
+        _ => DEFAULT_PROFILE.to_string(),
+    }
+}
+
+pub fn set_active(name: &str) -> LibrespotResult<()> {
+    fs::create_dir_all(config_dir())?;
+    fs::write(config_dir().join(ACTIVE_PROFILE_FILE), name)?;
+    Ok(())
+}
+
+pub fn create(name: &str) -> LibrespotResult<()> {
+    validate_name(name)?;
+    if exists(name) {
+        return Err(LibrespotError::IllegalConfig(format!(
+            "Profile {:?} already exists",
+            name
+        )));
+    }
+
+    info!("Creating profile {:?}", name);
+    let dirs = profile_dirs(name);
+    fs::create_dir_all(&dirs.config)?;
+    fs::create_dir_all(&dirs.cache)?;
+    Ok(())
+}
+
+/// Remove the directories of a profile
+///
+/// Credentials have to be wiped before, removing the files does not overwrite them.
+pub fn delete(name: &str) -> LibrespotResult<()> {
+    if name == DEFAULT_PROFILE {
+        return Err(LibrespotError::IllegalConfig(
+            "The default profile can not be deleted".to_string(),
+        ));
+    }
+    validate_name(name)?;
+
+    info!("Deleting profile {:?}", name);
+    let dirs = profile_dirs(name);
+    for dir in &[dirs.config, dirs.cache] {
+        match fs::remove_dir_all(dir) {
+            Ok(()) => (),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Names are used as directory names and in comma separated lists
+pub fn validate_name(name: &str) -> LibrespotResult<()> {
+    let valid = !name.trim().is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(LibrespotError::IllegalConfig(format!(
+            "Invalid profile name {:?}, use letters, digits, spaces, - and _",
+            name
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use super::*;
+
+    /// Points the XDG directories to a temporary directory for all tests of the process
+    fn use_temp_dirs() -> PathBuf {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            let dir = tempfile::tempdir().unwrap().into_path();
+            std::env::set_var("XDG_CONFIG_HOME", dir.join("config"));
+            std::env::set_var("XDG_CACHE_HOME", dir.join("cache"));
+        });
+        xdg_base_dirs::config_home().parent().unwrap().to_path_buf()
+    }
+
+    #[test]
+    fn validates_names() {
+        for name in &["work", "My Music", "kids_2", "a-b", "Café"] {
+            assert!(validate_name(name).is_ok(), "{:?}", name);
+        }
+        let too_long = "a".repeat(MAX_NAME_LEN + 1);
+        for name in &["", " ", "..", ".", "a/b", "a\\b", "a,b", &too_long] {
+            assert!(validate_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn keeps_profiles_in_their_own_directories() {
+        let root = use_temp_dirs();
+        let org_app = PathBuf::from(ORGANIZATION_NAME).join(APPLICATION_NAME);
+
+        let dirs = profile_dirs(DEFAULT_PROFILE);
+        assert_eq!(dirs.config, root.join("config").join(&org_app));
+        assert_eq!(dirs.cache, root.join("cache").join(&org_app));
+
+        let dirs = profile_dirs("layout");
+        assert_eq!(
+            dirs.config,
+            root.join("config").join(&org_app).join("profiles/layout")
+        );
+        assert_eq!(
+            dirs.cache,
+            root.join("cache").join(&org_app).join("profiles/layout")
+        );
+    }
+
+    #[test]
+    fn creates_and_deletes_profiles() {
+        use_temp_dirs();
+        assert!(!exists("travel"));
+        create("travel").unwrap();
+        assert!(exists("travel"));
+        assert!(profile_dirs("travel").config.is_dir());
+        assert!(profile_dirs("travel").cache.is_dir());
+        assert!(list().contains(&"travel".to_string()));
+        assert_eq!(list()[0], DEFAULT_PROFILE);
+
+        assert!(create("travel").is_err());
+        assert!(create("..").is_err());
+        assert!(create(DEFAULT_PROFILE).is_err());
+
+        delete("travel").unwrap();
+        assert!(!exists("travel"));
+        assert!(!profile_dirs("travel").cache.exists());
+        assert!(!list().contains(&"travel".to_string()));
+
+        assert!(delete(DEFAULT_PROFILE).is_err());
+        assert!(delete("a/b").is_err());
+        // deleting a missing profile is not an error
+        delete("travel").unwrap();
+    }
+
+    #[test]
+    fn remembers_the_active_profile() {
+        use_temp_dirs();
+        create("office").unwrap();
+        set_active("office").unwrap();
+        assert_eq!(active(), "office");
+
+        // a deleted profile falls back to the default one
+        delete("office").unwrap();
+        assert_eq!(active(), DEFAULT_PROFILE);
+    }
+}
@@ -0,0 +1,234 @@
+//! Reconnect policy
+//!
+//! Reconnects are scheduled with an exponentially growing, jittered delay. The attempts are counted
+//! until a connection was stable for a while, so a flaky network does not exhaust them.
+
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+/// Source of the current time, replaceable for tests
+pub trait Clock: RefUnwindSafe + UnwindSafe + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when told to
+#[cfg(test)]
+pub(crate) struct ManualClock(std::sync::Mutex<Instant>);
+
+#[cfg(test)]
+impl ManualClock {
+    pub(crate) fn new() -> Self {
+        Self(std::sync::Mutex::new(Instant::now()))
+    }
+
+    pub(crate) fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt
+    pub initial_delay: Duration,
+    /// Upper bound for the delay
+    pub max_delay: Duration,
+    /// Factor the delay grows with every attempt
+    pub multiplier: f64,
+    /// Fraction of the delay that is randomized (0-1)
+    pub jitter: f64,
+    /// Maximum number of attempts, `None` to never give up
+    pub max_attempts: Option<u32>,
+    /// A connection that lasted that long resets the attempts
+    pub reset_after: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5 * 60),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: Some(10),
+            reset_after: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before `attempt` (starting at 1) without jitter
+    #[must_use]
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(63) as i32;
+        let delay = self.initial_delay.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()))
+    }
+}
+
+/// A scheduled reconnect
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReconnectAttempt {
+    /// Number of the attempt, starting at 1
+    pub attempt: u32,
+    pub delay: Duration,
+}
+
+/// Tracks reconnect attempts according to a [`ReconnectPolicy`]
+pub struct Backoff {
+    policy: ReconnectPolicy,
+    clock: Arc<dyn Clock>,
+    attempts: u32,
+    connected_at: Option<Instant>,
+}
+
+impl Backoff {
+    #[must_use]
+    pub fn new(policy: ReconnectPolicy, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            policy,
+            clock,
+            attempts: 0,
+            connected_at: None,
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: ReconnectPolicy) {
+        self.policy = policy;
+    }
+
+    /// Forget previous attempts, e.g. after the network changed
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+        self.connected_at = None;
+    }
+
+    /// Record a successful connection
+    pub fn connected(&mut self) {
+        self.connected_at = Some(self.clock.now());
+    }
+
+    /// Record a lost connection or a failed attempt and schedule the next attempt
+    ///
+    /// Returns `None` when the policy gives up.
+    pub fn next_attempt(&mut self) -> Option<ReconnectAttempt> {
+        self.next_attempt_with_jitter(rand::thread_rng().gen_range(-1.0..=1.0))
+    }
+
+    /// Like [`Backoff::next_attempt`] with a given random value in the range -1-1
+    pub fn next_attempt_with_jitter(&mut self, random: f64) -> Option<ReconnectAttempt> {
+        if let Some(connected_at) = self.connected_at.take() {
+            if self.clock.now().saturating_duration_since(connected_at) >= self.policy.reset_after {
+                self.attempts = 0;
+            }
+        }
+
+        if let Some(max_attempts) = self.policy.max_attempts {
+            if self.attempts >= max_attempts {
+                return None;
+            }
+        }
+        self.attempts += 1;
+
+        let base = self.policy.base_delay(self.attempts).as_secs_f64();
+        let jitter = self.policy.jitter.clamp(0.0, 1.0) * random.clamp(-1.0, 1.0);
+        Some(ReconnectAttempt {
+            attempt: self.attempts,
+            delay: Duration::from_secs_f64((base * (1.0 + jitter)).max(0.0)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_backoff(policy: ReconnectPolicy) -> (Backoff, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new());
+        (Backoff::new(policy, clock.clone()), clock)
+    }
+
+    fn delays(backoff: &mut Backoff, count: usize) -> Vec<u64> {
+        (0..count)
+            .map(|_| {
+                backoff
+                    .next_attempt_with_jitter(0.0)
+                    .unwrap()
+                    .delay
+                    .as_secs()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delay_grows_up_to_the_maximum() {
+        let (mut backoff, _) = new_backoff(ReconnectPolicy {
+            max_delay: Duration::from_secs(10),
+            max_attempts: None,
+            ..ReconnectPolicy::default()
+        });
+        assert_eq!(delays(&mut backoff, 6), [1, 2, 4, 8, 10, 10]);
+    }
+
+    #[test]
+    fn jitter_stays_within_its_fraction() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_secs(10),
+            jitter: 0.2,
+            ..ReconnectPolicy::default()
+        };
+        let (mut backoff, _) = new_backoff(policy.clone());
+        let attempt = backoff.next_attempt_with_jitter(1.0).unwrap();
+        assert_eq!(attempt.attempt, 1);
+        assert_eq!(attempt.delay, Duration::from_secs(12));
+
+        let (mut backoff, _) = new_backoff(policy);
+        let attempt = backoff.next_attempt_with_jitter(-5.0).unwrap();
+        assert_eq!(attempt.delay, Duration::from_secs(8));
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        let (mut backoff, _) = new_backoff(ReconnectPolicy {
+            max_attempts: Some(3),
+            ..ReconnectPolicy::default()
+        });
+        assert_eq!(delays(&mut backoff, 3), [1, 2, 4]);
+        assert_eq!(backoff.next_attempt_with_jitter(0.0), None);
+
+        backoff.reset();
+        assert_eq!(delays(&mut backoff, 1), [1]);
+    }
+
+    #[test]
+    fn stable_connection_resets_attempts() {
+        let (mut backoff, clock) = new_backoff(ReconnectPolicy::default());
+        assert_eq!(delays(&mut backoff, 3), [1, 2, 4]);
+
+        // too short to count as stable
+        backoff.connected();
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(delays(&mut backoff, 1), [8]);
+
+        backoff.connected();
+        clock.advance(ReconnectPolicy::default().reset_after);
+        assert_eq!(delays(&mut backoff, 2), [1, 2]);
+    }
+}
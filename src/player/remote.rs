@@ -0,0 +1,472 @@
+//! Sends Spirc commands to our own device.
+//!
+//! `Spirc` only exposes a small set of commands (play, pause, skip and volume steps), these are
+//! called directly. Everything else (seek, volume, shuffle, repeat, load, ...) is sent as a Spirc
+//! frame to the user's remote channel, addressed to our device, exactly like a remote Connect
+//! client would do. The response only tells that the Spotify server accepted the frame.
+//!
+//! The state of our device is observed the same way: Spirc broadcasts its state on the same channel.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use librespot_core::mercury::{MercuryFuture, MercuryResponse};
+use librespot_core::session::Session;
+use librespot_core::version;
+use librespot_protocol::spirc::{Frame, MessageType, PlayStatus, State, TrackRef};
+use log::{error, info, warn};
+use protobuf::{Message, RepeatedField};
+use quick_error::quick_error;
+use rand::seq::SliceRandom;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::watch;
+use url::form_urlencoded;
+
+use crate::player::commands::{CommandError, CommandResult};
+use crate::player::events::{LibrespotEvent, LibrespotEventListenerRef};
+use crate::player::RepeatMode;
+
+/// Time a new Spirc has to announce itself before its state is restored anyway
+const SPIRC_HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Context pages requested at most for one load, guards against pages linking in a loop
+const MAX_CONTEXT_REQUESTS: usize = 100;
+
+pub type SharedSpircState = Arc<Mutex<Option<State>>>;
+
+/// Counts the hello frames of our device
+///
+/// A new Spirc sends a hello as soon as it listens on the remote channel.
+pub type SpircHellos = Arc<watch::Sender<u64>>;
+
+fn remote_uri(session: &Session) -> String {
+    let username: String = form_urlencoded::byte_serialize(session.username().as_bytes()).collect();
+    format!("hm://remote/user/{}/", username)
+}
+
+pub struct SpircRemote {
+    session: Session,
+    uri: String,
+    ident: String,
+    recipient: String,
+    seq_nr: u32,
+}
+
+impl SpircRemote {
+    #[must_use]
+    pub fn new(session: Session) -> Self {
+        let recipient = session.device_id().to_string();
+        Self {
+            uri: remote_uri(&session),
+            // Spirc ignores frames with its own ident
+            ident: format!("{}-remote", recipient),
+            recipient,
+            session,
+            seq_nr: 0,
+        }
+    }
+
+    pub fn seek(&mut self, position_ms: u32) -> MercuryFuture<MercuryResponse> {
+        let mut frame = self.new_frame(MessageType::kMessageTypeSeek);
+        frame.set_position(position_ms);
+        self.send(frame)
+    }
+
+    pub fn set_volume(&mut self, volume: u16) -> MercuryFuture<MercuryResponse> {
+        let mut frame = self.new_frame(MessageType::kMessageTypeVolume);
+        frame.set_volume(u32::from(volume));
+        self.send(frame)
+    }
+
+    pub fn set_shuffle(&mut self, shuffle: bool) -> MercuryFuture<MercuryResponse> {
+        let mut frame = self.new_frame(MessageType::kMessageTypeShuffle);
+        frame.mut_state().set_shuffle(shuffle);
+        self.send(frame)
+    }
+
+    pub fn set_repeat(&mut self, repeat: bool) -> MercuryFuture<MercuryResponse> {
+        let mut frame = self.new_frame(MessageType::kMessageTypeRepeat);
+        frame.mut_state().set_repeat(repeat);
+        self.send(frame)
+    }
+
+    /// Load a context or a list of tracks and start playing.
+    ///
+    /// If `track_uris` is empty, the tracks are resolved from `context_uri`.
+    pub fn load(
+        &mut self,
+        context_uri: String,
+        track_uris: Vec<String>,
+        start_index: u32,
+        position_ms: u32,
+        shuffle: bool,
+    ) -> impl Future<Output = CommandResult> + 'static {
+        let mut frame = self.new_frame(MessageType::kMessageTypeLoad);
+        let session = self.session.clone();
+        let uri = self.uri.clone();
+
+        async move {
+            let track_uris = if track_uris.is_empty() {
+                match resolve_context(&session, &context_uri).await {
+                    Ok(track_uris) => track_uris,
+                    Err(err) => {
+                        error!("Could not resolve context {}: {}", context_uri, err);
+                        return Err(CommandError::Rejected(format!(
+                            "Could not resolve {}: {}",
+                            context_uri, err
+                        )));
+                    }
+                }
+            } else {
+                track_uris
+            };
+            if track_uris.is_empty() {
+                warn!("Nothing to load");
+                return Err(CommandError::Rejected("Nothing to load".to_string()));
+            }
+
+            let mut tracks: Vec<TrackRef> = track_uris
+                .into_iter()
+                .map(|uri| {
+                    let mut track = TrackRef::new();
+                    track.set_uri(uri);
+                    track
+                })
+                .collect();
+            let mut start_index = (start_index as usize).min(tracks.len() - 1);
+            if shuffle {
+                tracks.swap(0, start_index);
+                tracks[1..].shuffle(&mut rand::thread_rng());
+                start_index = 0;
+            }
+            info!(
+                "Loading {} tracks of context {:?}",
+                tracks.len(),
+                context_uri
+            );
+
+            let state = frame.mut_state();
+            state.set_context_uri(context_uri);
+            state.set_track(RepeatedField::from_vec(tracks));
+            state.set_playing_track_index(start_index as u32);
+            state.set_position_ms(position_ms);
+            state.set_status(PlayStatus::kPlayStatusPlay);
+            state.set_shuffle(shuffle);
+
+            let data = serialize(&frame);
+            await_response(session.mercury().send(uri, data)).await
+        }
+    }
+
+    /// Load a state previously reported by another Spirc instance
+    ///
+    /// Used to continue playback at the current position after the Spirc was replaced. The state
+    /// is sent once the new Spirc said hello, `hellos` has to be subscribed before it was started.
+    pub fn restore(
+        &mut self,
+        mut state: State,
+        mut hellos: watch::Receiver<u64>,
+    ) -> impl Future<Output = ()> + 'static {
+        let mut frame = self.new_frame(MessageType::kMessageTypeLoad);
+        let session = self.session.clone();
+        let uri = self.uri.clone();
+
+        if state.get_status() == PlayStatus::kPlayStatusPlay {
+            let elapsed = self.now_ms() - state.get_position_measured_at() as i64;
+            let position_ms = i64::from(state.get_position_ms()) + elapsed.max(0);
+            state.set_position_ms(position_ms.min(i64::from(u32::MAX)) as u32);
+        }
+
+        async move {
+            if tokio::time::timeout(SPIRC_HELLO_TIMEOUT, hellos.changed())
+                .await
+                .is_err()
+            {
+                warn!("Spirc did not say hello, restoring its state anyway");
+            }
+
+            info!(
+                "Restoring track {} of context {:?} at {} ms",
+                state.get_playing_track_index(),
+                state.get_context_uri(),
+                state.get_position_ms()
+            );
+            frame.set_state(state);
+            // failures are logged, there is nobody to report them to
+            let _ = await_response(session.mercury().send(uri, serialize(&frame))).await;
+        }
+    }
+
+    fn new_frame(&mut self, typ: MessageType) -> Frame {
+        self.seq_nr = self.seq_nr.wrapping_add(1);
+
+        let mut frame = Frame::new();
+        frame.set_version(1);
+        frame.set_protocol_version("2.0.0".to_string());
+        frame.set_ident(self.ident.clone());
+        frame.set_seq_nr(self.seq_nr);
+        frame.set_typ(typ);
+        frame.set_state_update_id(self.now_ms());
+        frame
+            .mut_device_state()
+            .set_sw_version(version::VERSION_STRING.to_string());
+        frame.mut_recipient().push(self.recipient.clone());
+        frame
+    }
+
+    fn send(&self, frame: Frame) -> MercuryFuture<MercuryResponse> {
+        self.session
+            .mercury()
+            .send(self.uri.clone(), serialize(&frame))
+    }
+
+    fn now_ms(&self) -> i64 {
+        let dur = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(dur) => dur,
+            Err(err) => err.duration(),
+        };
+
+        dur.as_millis() as i64 + 1000 * self.session.time_delta()
+    }
+}
+
+fn serialize(frame: &Frame) -> Vec<u8> {
+    frame.write_to_bytes().unwrap_or_else(|err| {
+        error!("Failed to serialize Spirc frame: {:?}", err);
+        Vec::new()
+    })
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ResolveError {
+        Request(url: String) {
+            display("Request for {} failed", url)
+        }
+        EmptyResponse(url: String) {
+            display("Empty response for {}", url)
+        }
+        InvalidResponse(url: String, err: serde_json::Error) {
+            display("Invalid response for {}: {}", url, err)
+            source(err)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolvedContext {
+    #[serde(default)]
+    pages: Vec<ContextPage>,
+}
+
+/// A page of a context, its tracks may have to be loaded from `page_url`
+#[derive(Debug, Default, Deserialize)]
+struct ContextPage {
+    #[serde(default)]
+    tracks: Vec<ContextTrack>,
+    #[serde(default)]
+    page_url: Option<String>,
+    #[serde(default)]
+    next_page_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContextTrack {
+    #[serde(default)]
+    uri: Option<String>,
+}
+
+/// Resolve the track URIs of a context (album, playlist, ...)
+///
+/// Follows the page URLs, large playlists are split across pages.
+async fn resolve_context(
+    session: &Session,
+    context_uri: &str,
+) -> Result<Vec<String>, ResolveError> {
+    let context: ResolvedContext =
+        fetch_json(session, format!("hm://context-resolve/v1/{}", context_uri)).await?;
+
+    let mut track_uris = Vec::new();
+    let mut requests = 1;
+    for page in context.pages {
+        let mut page = Some(page);
+        while let Some(mut current) = page.take() {
+            let url = if current.tracks.is_empty() {
+                current.page_url.take()
+            } else {
+                None
+            };
+            let url = url.or_else(|| {
+                track_uris.extend(current.tracks.drain(..).filter_map(|track| track.uri));
+                current.next_page_url.take()
+            });
+
+            if let Some(url) = url {
+                if requests >= MAX_CONTEXT_REQUESTS {
+                    warn!("Context {} has too many pages, truncating", context_uri);
+                    return Ok(track_uris);
+                }
+                requests += 1;
+                page = Some(fetch_json(session, url).await?);
+            }
+        }
+    }
+    Ok(track_uris)
+}
+
+async fn fetch_json<T: DeserializeOwned>(
+    session: &Session,
+    url: String,
+) -> Result<T, ResolveError> {
+    let response = session
+        .mercury()
+        .get(url.clone())
+        .await
+        .map_err(|_| ResolveError::Request(url.clone()))?;
+    parse_json(url, response.payload.first().map(Vec::as_slice))
+}
+
+fn parse_json<T: DeserializeOwned>(url: String, data: Option<&[u8]>) -> Result<T, ResolveError> {
+    let data = match data {
+        Some(data) => data,
+        None => return Err(ResolveError::EmptyResponse(url)),
+    };
+    serde_json::from_slice(data).map_err(|err| ResolveError::InvalidResponse(url, err))
+}
+
+pub async fn await_response(response: MercuryFuture<MercuryResponse>) -> CommandResult {
+    match response.await {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            error!("Spirc command was not delivered");
+            Err(CommandError::Rejected(
+                "Spirc command was not delivered".to_string(),
+            ))
+        }
+    }
+}
+
+/// Reports shuffle and repeat changes of our device, regardless of who changed them.
+///
+/// The last reported state is kept in `last_state` while our device is active. Hello frames of
+/// our device are counted in `hellos`. The subscription is requested right away, so a Spirc
+/// started after this call is seen from its hello on.
+pub fn watch_state(
+    session: Session,
+    listener: LibrespotEventListenerRef,
+    last_state: SharedSpircState,
+    hellos: SpircHellos,
+) -> impl Future<Output = ()> + 'static {
+    let subscription = session.mercury().subscribe(remote_uri(&session));
+    async move {
+        match subscription.await {
+            Ok(subscription) => {
+                handle_state_frames(session, subscription, listener, last_state, hellos).await;
+            }
+            Err(_) => warn!("Could not subscribe to Spirc state"),
+        }
+    }
+}
+
+async fn handle_state_frames(
+    session: Session,
+    mut subscription: UnboundedReceiver<MercuryResponse>,
+    listener: LibrespotEventListenerRef,
+    last_state: SharedSpircState,
+    hellos: SpircHellos,
+) {
+    let mut shuffle: Option<bool> = None;
+    let mut repeat: Option<bool> = None;
+    while let Some(response) = subscription.recv().await {
+        let frame = match response
+            .payload
+            .first()
+            .map(|data| Frame::parse_from_bytes(data))
+        {
+            Some(Ok(frame)) => frame,
+            _ => continue,
+        };
+        if frame.get_ident() != session.device_id() {
+            continue;
+        }
+        if frame.get_typ() == MessageType::kMessageTypeHello {
+            let count = *hellos.borrow() + 1;
+            hellos.send_replace(count);
+        }
+        if !frame.has_state() {
+            *last_state.lock().unwrap() = None;
+            continue;
+        }
+
+        let state = frame.get_state();
+        *last_state.lock().unwrap() = Some(state.clone());
+        if shuffle != Some(state.get_shuffle()) {
+            shuffle = Some(state.get_shuffle());
+            listener.notify(LibrespotEvent::ShuffleChanged {
+                shuffle: state.get_shuffle(),
+            });
+        }
+        if repeat != Some(state.get_repeat()) {
+            repeat = Some(state.get_repeat());
+            listener.notify(LibrespotEvent::RepeatChanged {
+                repeat: if state.get_repeat() {
+                    RepeatMode::Context
+                } else {
+                    RepeatMode::Off
+                },
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pages_with_and_without_tracks() {
+        let data = br#"{
+            "uri": "spotify:playlist:1",
+            "pages": [
+                {
+                    "tracks": [{"uri": "spotify:track:a"}, {"uid": "no-uri"}],
+                    "next_page_url": "hm://context-resolve/v1/page/2"
+                },
+                {"page_url": "hm://context-resolve/v1/page/3"}
+            ]
+        }"#;
+
+        let context: ResolvedContext = parse_json("url".to_string(), Some(data)).unwrap();
+        assert_eq!(context.pages.len(), 2);
+        assert_eq!(context.pages[0].tracks.len(), 2);
+        assert_eq!(
+            context.pages[0].tracks[0].uri.as_deref(),
+            Some("spotify:track:a")
+        );
+        assert_eq!(context.pages[0].tracks[1].uri, None);
+        assert_eq!(
+            context.pages[0].next_page_url.as_deref(),
+            Some("hm://context-resolve/v1/page/2")
+        );
+        assert!(context.pages[1].tracks.is_empty());
+        assert_eq!(
+            context.pages[1].page_url.as_deref(),
+            Some("hm://context-resolve/v1/page/3")
+        );
+    }
+
+    #[test]
+    fn reports_the_cause_of_invalid_responses() {
+        let err = parse_json::<ResolvedContext>("hm://x".to_string(), Some(b"{\"pages\": 1}"))
+            .unwrap_err();
+        assert!(matches!(err, ResolveError::InvalidResponse(..)));
+        assert!(err
+            .to_string()
+            .starts_with("Invalid response for hm://x: invalid type"));
+
+        let err = parse_json::<ResolvedContext>("hm://x".to_string(), None).unwrap_err();
+        assert!(matches!(err, ResolveError::EmptyResponse(_)));
+    }
+}
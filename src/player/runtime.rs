@@ -1,6 +1,7 @@
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use std::{fs, io, panic, thread};
+use std::time::Duration;
+use std::{panic, thread};
 
 use futures::channel::mpsc::{unbounded, UnboundedSender};
 use librespot_core::authentication::Credentials;
@@ -10,21 +11,27 @@ use librespot_core::version;
 use librespot_playback::config::{PlayerConfig, VolumeCtrl};
 use librespot_playback::mixer::MixerConfig;
 use librespot_playback::{audio_backend, mixer};
-use log::{error, info, warn};
+use log::{info, warn};
 use tokio::runtime::Builder;
 
-use crate::player::controller::{ControlMessage, LibrespotConfig, LibrespotController};
+use crate::player::commands::{Command, CommandError, CommandResult, RequestId};
+use crate::player::controller::{
+    ControlMessage, LibrespotConfig, LibrespotController, PlaybackConfig,
+};
+use crate::player::credentials::{create_private_dir, CredentialManager};
 use crate::player::error::{LibrespotError, LibrespotResult};
 use crate::player::events::{LibrespotEvent, LibrespotEventListener};
-use crate::player::options::Options;
-
-fn setup(opts: Options) -> LibrespotResult<LibrespotConfig> {
-    info!(
-        "sailify/{} librespot/{}",
-        env!("CARGO_PKG_VERSION"),
-        version::SEMVER,
-    );
+use crate::player::options::{Options, SettingsChanges};
+use crate::player::proxy::ProxyConfig;
+use crate::player::reconnect::{ReconnectPolicy, SystemClock};
+use crate::player::secret::SecretCredentials;
+use crate::player::state::SharedState;
+use crate::player::volume;
 
+/// Build the player and Spirc configuration
+///
+/// The initial volume is only taken from `opts`, the cached volume is added by the caller.
+pub fn setup_playback(opts: &Options) -> LibrespotResult<PlaybackConfig> {
     let backend = audio_backend::find(opts.backend.clone()).ok_or_else(|| {
         LibrespotError::IllegalConfig(format!("Invalid backend {:?}", &opts.backend))
     })?;
@@ -33,34 +40,20 @@ fn setup(opts: Options) -> LibrespotResult<LibrespotConfig> {
         .ok_or_else(|| LibrespotError::IllegalConfig(format!("Invalid mixer {:?}", &opts.mixer)))?;
 
     let mixer_config = MixerConfig {
-        device: opts.mixer_card,
+        device: opts.mixer_card.clone(),
         index: opts.mixer_index,
-        control: opts.mixer_name,
-        volume_ctrl: VolumeCtrl::default(),
+        control: opts.mixer_name.clone(),
+        volume_ctrl: opts.volume_ctrl,
     };
 
-    let cache = Cache::new(opts.system_cache, opts.audio_cache, opts.cache_size_limit)?;
-
-    let initial_volume = opts
-        .initial_volume
-        .map(|volume| {
-            assert!(volume <= 100, "Initial volume must be in the range 0-100");
-            (i32::from(volume) * 0xFFFF / 100) as u16
-        })
-        .or_else(|| cache.volume());
-
-    let credentials = match (opts.username, opts.password) {
-        (Some(username), Some(password)) => Credentials::with_password(username, password),
-        _ => cache
-            .credentials()
-            .ok_or(LibrespotError::MissingCredentials)?,
-    };
-
-    let session_config = SessionConfig {
-        user_agent: version::VERSION_STRING.to_string(),
-        device_id: opts.device_id,
-        proxy: None,
-        ap_port: opts.ap_port,
+    let initial_volume = match opts.initial_volume {
+        Some(volume) if volume > 100 => {
+            return Err(LibrespotError::IllegalConfig(format!(
+                "Initial volume must be in the range 0-100, got {}",
+                volume
+            )));
+        }
+        volume => volume.map(volume::percent_to_raw),
     };
 
     let player_config = PlayerConfig {
@@ -74,49 +67,100 @@ fn setup(opts: Options) -> LibrespotResult<LibrespotConfig> {
     };
 
     let connect_config = ConnectConfig {
-        name: opts.device_name,
+        name: opts.device_name.clone(),
         device_type: DeviceType::Smartphone,
         initial_volume,
         has_volume_ctrl: !matches!(mixer_config.volume_ctrl, VolumeCtrl::Fixed),
         autoplay: opts.autoplay,
     };
 
-    Ok(LibrespotConfig {
+    Ok(PlaybackConfig {
         format: opts.format,
         backend,
-        cache,
-        session_config,
+        device: opts.backend_device.clone(),
+        mixer,
         player_config,
         connect_config,
-        credentials,
-        device: opts.backend_device,
-        mixer,
         mixer_config,
     })
 }
 
+fn setup(opts: Options, credential_manager: CredentialManager) -> LibrespotResult<LibrespotConfig> {
+    info!(
+        "sailify/{} librespot/{}",
+        env!("CARGO_PKG_VERSION"),
+        version::SEMVER,
+    );
+
+    let mut playback = setup_playback(&opts)?;
+
+    // librespot stores the reusable credentials in plaintext there until they are taken over
+    if let Some(system_cache) = &opts.system_cache {
+        create_private_dir(system_cache)?;
+    }
+    let cache = Cache::new(opts.system_cache, opts.audio_cache, opts.cache_size_limit)?;
+
+    let connect_config = &mut playback.connect_config;
+    connect_config.initial_volume = connect_config.initial_volume.or_else(|| cache.volume());
+
+    let credentials = match (opts.credentials, opts.username, opts.password) {
+        (Some(credentials), _, _) => Some(credentials),
+        (None, Some(username), Some(password)) => {
+            Some(Credentials::with_password(username, password.expose()).into())
+        }
+        _ => credential_manager
+            .load()
+            .unwrap_or_else(|err| {
+                warn!("Could not load stored credentials: {}", err);
+                None
+            })
+            .map(SecretCredentials::from),
+    };
+    if credentials.is_none() && !opts.discovery {
+        return Err(LibrespotError::MissingCredentials);
+    }
+
+    let proxy = opts
+        .proxy
+        .as_deref()
+        .filter(|proxy| !proxy.is_empty())
+        .map(ProxyConfig::parse)
+        .transpose()?;
+
+    let session_config = SessionConfig {
+        user_agent: version::VERSION_STRING.to_string(),
+        device_id: opts.device_id,
+        // the URL of the proxy bridge, set once it runs
+        proxy: None,
+        ap_port: opts.ap_port,
+    };
+
+    Ok(LibrespotConfig {
+        playback,
+        cache,
+        proxy,
+        session_config,
+        credentials,
+        discovery: opts.discovery,
+        credential_manager,
+        reconnect_policy: opts.reconnect_policy,
+        clock: Arc::new(SystemClock),
+    })
+}
+
 pub struct PlayerRuntime {
     handle: JoinHandle<()>,
     control: UnboundedSender<ControlMessage>,
 }
 
 impl PlayerRuntime {
-    pub fn remove_credentials(opts: &Options) {
-        if let Some(system_cache) = &opts.system_cache {
-            match fs::remove_file(system_cache.join("credentials.json")) {
-                Ok(_) => (),
-                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
-                // TODO: what should we do?
-                Err(err) => error!("Failed to remove credentials: {:?}", err),
-            };
-        }
-    }
-
     pub fn start(
         listener: Arc<dyn LibrespotEventListener>,
+        state: SharedState,
         options: Options,
+        credential_manager: CredentialManager,
     ) -> LibrespotResult<Self> {
-        let setup = setup(options)?;
+        let setup = setup(options, credential_manager)?;
 
         let (control_tx, control_rx) = unbounded();
         let control_tx_ = control_tx.clone();
@@ -141,6 +185,7 @@ impl PlayerRuntime {
                         control_tx,
                         control_rx,
                         listener_clone,
+                        state,
                         setup,
                     );
                     core.block_on(controller_future);
@@ -168,12 +213,23 @@ impl PlayerRuntime {
         })
     }
 
+    /// The runtime ended on its own, after giving up or a crash
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
     pub fn shutdown(self) {
-        if self
-            .control
-            .unbounded_send(ControlMessage::Shutdown)
-            .is_err()
-        {
+        self.stop_with(ControlMessage::Shutdown);
+    }
+
+    /// Remove the stored credentials and shut down, even while logging in
+    pub fn logout(self) {
+        self.stop_with(ControlMessage::Logout);
+    }
+
+    fn stop_with(self, msg: ControlMessage) {
+        if self.control.unbounded_send(msg).is_err() {
             warn!("Shutdown could not send because thread is already dead");
         } else {
             info!("join shutdown");
@@ -182,20 +238,39 @@ impl PlayerRuntime {
         }
     }
 
-    pub fn play(&self) {
-        let _ = self.control.unbounded_send(ControlMessage::Play);
+    /// Send a playback command, fails if the runtime is dead
+    pub fn command(&self, request_id: RequestId, command: Command) -> CommandResult {
+        self.control
+            .unbounded_send(ControlMessage::Command {
+                request_id,
+                command,
+            })
+            .map_err(|_| CommandError::NotRunning)
     }
 
-    pub fn pause(&self) {
-        let _ = self.control.unbounded_send(ControlMessage::Pause);
+    pub fn reconfigure(
+        &self,
+        playback: PlaybackConfig,
+        changes: SettingsChanges,
+        reconnect_policy: ReconnectPolicy,
+    ) {
+        let _ = self.control.unbounded_send(ControlMessage::Reconfigure {
+            playback: Box::new(playback),
+            changes,
+            reconnect_policy,
+        });
     }
 
-    pub fn next(&self) {
-        let _ = self.control.unbounded_send(ControlMessage::Next);
+    pub fn set_position_interval(&self, interval: Option<Duration>) {
+        let _ = self
+            .control
+            .unbounded_send(ControlMessage::SetPositionInterval { interval });
     }
 
-    pub fn previous(&self) {
-        let _ = self.control.unbounded_send(ControlMessage::Previous);
+    pub fn network_changed(&self, online: bool, metered: bool) {
+        let _ = self
+            .control
+            .unbounded_send(ControlMessage::NetworkChanged { online, metered });
     }
 
     pub fn refresh_token(&self) {
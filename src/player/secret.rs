@@ -0,0 +1,113 @@
+//! Secrets in memory
+//!
+//! Passwords and tokens are overwritten with zeros when they are dropped, so they don't linger in
+//! freed memory.
+
+use std::fmt;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+use librespot_core::authentication::Credentials;
+use serde::{Deserialize, Deserializer};
+
+/// Overwrite `bytes` with zeros in a way the compiler does not optimize away
+pub fn zeroize(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        // SAFETY: `byte` is a valid, aligned reference
+        unsafe { std::ptr::write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Overwrite the authentication data of `credentials`
+pub fn zeroize_credentials(credentials: &mut Credentials) {
+    zeroize(&mut credentials.auth_data);
+}
+
+/// A string that is overwritten when dropped
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    #[must_use]
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    #[must_use]
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        // SAFETY: only zeros are written, which is valid UTF-8
+        zeroize(unsafe { self.0.as_mut_vec() });
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(***)")
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Credentials whose authentication data is overwritten when dropped, also for every clone
+#[derive(Clone)]
+pub struct SecretCredentials(Credentials);
+
+impl SecretCredentials {
+    #[must_use]
+    pub fn new(credentials: Credentials) -> Self {
+        Self(credentials)
+    }
+
+    #[must_use]
+    pub fn expose(&self) -> &Credentials {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn username(&self) -> &str {
+        &self.0.username
+    }
+}
+
+impl Drop for SecretCredentials {
+    fn drop(&mut self) {
+        zeroize_credentials(&mut self.0);
+    }
+}
+
+impl From<Credentials> for SecretCredentials {
+    fn from(credentials: Credentials) -> Self {
+        Self::new(credentials)
+    }
+}
+
+impl fmt::Debug for SecretCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretCredentials")
+            .field("username", &self.0.username)
+            .field("auth_type", &self.0.auth_type)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,479 @@
+//! Player state
+//!
+//! The state is tracked from the events of the runtime, so that it can be queried at any time
+//! instead of being rebuilt from the callbacks by every consumer.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use crate::player::events::{LibrespotEvent, LibrespotEventListener, LibrespotEventListenerRef};
+use crate::player::{ConnectionStatus, MediaStatus, PlayerState, RepeatMode};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateSnapshot {
+    pub player_state: PlayerState,
+    pub media_status: MediaStatus,
+    pub connection_status: ConnectionStatus,
+    pub track_uri: Option<String>,
+    pub play_request_id: Option<u64>,
+    pub position_ms: u32,
+    /// When `position_ms` was reported while playing
+    pub position_measured_at: Option<Instant>,
+    pub duration_ms: u32,
+    /// Raw volume (0-65535)
+    pub volume: u16,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+}
+
+impl Default for StateSnapshot {
+    fn default() -> Self {
+        Self {
+            player_state: PlayerState::Stopped,
+            media_status: MediaStatus::NoMedia,
+            connection_status: ConnectionStatus::Disconnected,
+            track_uri: None,
+            play_request_id: None,
+            position_ms: 0,
+            position_measured_at: None,
+            duration_ms: 0,
+            volume: 0,
+            shuffle: false,
+            repeat: RepeatMode::Off,
+        }
+    }
+}
+
+impl StateSnapshot {
+    fn set_track(
+        &mut self,
+        play_request_id: u64,
+        track_uri: &str,
+        player_state: PlayerState,
+        media_status: MediaStatus,
+    ) {
+        self.play_request_id = Some(play_request_id);
+        self.track_uri = Some(track_uri.to_string());
+        self.player_state = player_state;
+        self.media_status = media_status;
+        self.position_measured_at = None;
+    }
+
+    /// Position at `now`, interpolated while playing and clamped to the duration
+    #[must_use]
+    pub fn position_at(&self, now: Instant) -> u32 {
+        let position_ms = match self.position_measured_at {
+            Some(measured_at) if self.player_state == PlayerState::Playing => {
+                let elapsed_ms = now.saturating_duration_since(measured_at).as_millis() as u64;
+                u64::from(self.position_ms).saturating_add(elapsed_ms)
+            }
+            _ => u64::from(self.position_ms),
+        };
+        let max_position_ms = if self.duration_ms > 0 {
+            self.duration_ms
+        } else {
+            u32::MAX
+        };
+        position_ms.min(u64::from(max_position_ms)) as u32
+    }
+
+    /// Update the state from an event
+    pub fn apply(&mut self, evt: &LibrespotEvent) {
+        self.apply_at(evt, Instant::now());
+    }
+
+    /// Update the state from an event received at `now`
+    pub fn apply_at(&mut self, evt: &LibrespotEvent, now: Instant) {
+        match evt {
+            LibrespotEvent::Stopped {
+                play_request_id,
+                track_id,
+            } => {
+                self.set_track(
+                    *play_request_id,
+                    track_id,
+                    PlayerState::Stopped,
+                    MediaStatus::NoMedia,
+                );
+                self.position_ms = 0;
+                self.duration_ms = 0;
+            }
+            LibrespotEvent::Changed { new_track_id } => {
+                self.track_uri = Some(new_track_id.clone());
+            }
+            LibrespotEvent::Loading {
+                play_request_id,
+                track_id,
+                position_ms,
+            } => {
+                self.set_track(
+                    *play_request_id,
+                    track_id,
+                    PlayerState::Stopped,
+                    MediaStatus::Loading,
+                );
+                self.position_ms = *position_ms;
+                self.duration_ms = 0;
+            }
+            LibrespotEvent::Playing {
+                play_request_id,
+                track_id,
+                position_ms,
+                duration_ms,
+            } => {
+                self.set_track(
+                    *play_request_id,
+                    track_id,
+                    PlayerState::Playing,
+                    MediaStatus::Loaded,
+                );
+                self.position_ms = *position_ms;
+                self.position_measured_at = Some(now);
+                self.duration_ms = *duration_ms;
+            }
+            LibrespotEvent::Paused {
+                play_request_id,
+                track_id,
+                position_ms,
+                duration_ms,
+            } => {
+                self.set_track(
+                    *play_request_id,
+                    track_id,
+                    PlayerState::Paused,
+                    MediaStatus::Loaded,
+                );
+                self.position_ms = *position_ms;
+                self.duration_ms = *duration_ms;
+            }
+            LibrespotEvent::Unavailable {
+                play_request_id,
+                track_id,
+            } => {
+                self.set_track(
+                    *play_request_id,
+                    track_id,
+                    PlayerState::Stopped,
+                    MediaStatus::InvalidMedia,
+                );
+                self.position_ms = 0;
+                self.duration_ms = 0;
+            }
+            LibrespotEvent::VolumeSet { volume } => self.volume = *volume,
+            LibrespotEvent::ShuffleChanged { shuffle } => self.shuffle = *shuffle,
+            LibrespotEvent::RepeatChanged { repeat } => self.repeat = *repeat,
+            LibrespotEvent::Connecting | LibrespotEvent::StartReconnect => {
+                self.connection_status = ConnectionStatus::Connecting;
+            }
+            LibrespotEvent::Connected => self.connection_status = ConnectionStatus::Connected,
+            LibrespotEvent::WaitingForDiscovery => {
+                self.connection_status = ConnectionStatus::WaitingForDiscovery;
+            }
+            LibrespotEvent::ConnectionError { .. } => {
+                self.connection_status = ConnectionStatus::Disconnected;
+            }
+            LibrespotEvent::Error { err } if err.is_connection_error() => {
+                self.connection_status = ConnectionStatus::Disconnected;
+            }
+            LibrespotEvent::Shutdown => {
+                *self = Self {
+                    volume: self.volume,
+                    ..Self::default()
+                };
+            }
+            LibrespotEvent::Panic { .. } => {
+                self.player_state = PlayerState::Stopped;
+                self.connection_status = ConnectionStatus::Crashed;
+            }
+            LibrespotEvent::Position { .. }
+            | LibrespotEvent::ReconnectScheduled { .. }
+            | LibrespotEvent::TokenChanged { .. }
+            | LibrespotEvent::Error { .. }
+            | LibrespotEvent::SettingsApplied { .. }
+            | LibrespotEvent::OAuthCompleted
+            | LibrespotEvent::RemoteLogin { .. }
+            | LibrespotEvent::AccountInfo { .. }
+            | LibrespotEvent::LoginProgress { .. }
+            | LibrespotEvent::CommandResult { .. }
+            | LibrespotEvent::StateChanged { .. } => (),
+        }
+    }
+}
+
+pub type SharedState = Arc<Mutex<StateSnapshot>>;
+
+/// Keeps the state up to date and reports changes with a `StateChanged` event
+pub struct StateTracker {
+    state: SharedState,
+    listener: LibrespotEventListenerRef,
+}
+
+impl StateTracker {
+    #[must_use]
+    pub fn new(state: SharedState, listener: LibrespotEventListenerRef) -> Self {
+        Self { state, listener }
+    }
+}
+
+impl LibrespotEventListener for StateTracker {
+    fn notify(&self, evt: LibrespotEvent) {
+        let changed = {
+            let mut state = self.state.lock().unwrap();
+            let old_state = state.clone();
+            state.apply(&evt);
+            Some(state.clone()).filter(|state| *state != old_state)
+        };
+
+        self.listener.notify(evt);
+        if let Some(state) = changed {
+            self.listener.notify(LibrespotEvent::StateChanged { state });
+        }
+    }
+}
+
+/// Report the position every `interval` while playing
+pub async fn run_position_ticker(
+    state: SharedState,
+    listener: LibrespotEventListenerRef,
+    interval: Duration,
+) {
+    info!("Reporting position every {:?}", interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+
+        let position_ms = {
+            let state = state.lock().unwrap();
+            Some(state.position_at(Instant::now()))
+                .filter(|_| state.player_state == PlayerState::Playing)
+        };
+        if let Some(position_ms) = position_ms {
+            listener.notify(LibrespotEvent::Position { position_ms });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::error::LibrespotError;
+
+    fn playing(position_ms: u32) -> LibrespotEvent {
+        LibrespotEvent::Playing {
+            play_request_id: 1,
+            track_id: "spotify:track:a".to_string(),
+            position_ms,
+            duration_ms: 180_000,
+        }
+    }
+
+    fn applied(events: &[LibrespotEvent]) -> StateSnapshot {
+        let mut state = StateSnapshot::default();
+        for evt in events {
+            state.apply(evt);
+        }
+        state
+    }
+
+    #[test]
+    fn follows_the_track() {
+        let state = applied(&[LibrespotEvent::Loading {
+            play_request_id: 1,
+            track_id: "spotify:track:a".to_string(),
+            position_ms: 1000,
+        }]);
+        assert_eq!(state.player_state, PlayerState::Stopped);
+        assert_eq!(state.media_status, MediaStatus::Loading);
+        assert_eq!(state.track_uri.as_deref(), Some("spotify:track:a"));
+        assert_eq!(state.position_ms, 1000);
+
+        let state = applied(&[playing(1000)]);
+        assert_eq!(state.player_state, PlayerState::Playing);
+        assert_eq!(state.media_status, MediaStatus::Loaded);
+        assert_eq!(state.duration_ms, 180_000);
+        assert!(state.position_measured_at.is_some());
+
+        let state = applied(&[
+            playing(1000),
+            LibrespotEvent::Paused {
+                play_request_id: 1,
+                track_id: "spotify:track:a".to_string(),
+                position_ms: 2000,
+                duration_ms: 180_000,
+            },
+        ]);
+        assert_eq!(state.player_state, PlayerState::Paused);
+        assert_eq!(state.position_ms, 2000);
+        assert_eq!(state.position_measured_at, None);
+
+        let state = applied(&[
+            playing(1000),
+            LibrespotEvent::Changed {
+                new_track_id: "spotify:track:b".to_string(),
+            },
+        ]);
+        assert_eq!(state.track_uri.as_deref(), Some("spotify:track:b"));
+        assert_eq!(state.player_state, PlayerState::Playing);
+
+        let state = applied(&[
+            playing(1000),
+            LibrespotEvent::Unavailable {
+                play_request_id: 2,
+                track_id: "spotify:track:b".to_string(),
+            },
+        ]);
+        assert_eq!(state.media_status, MediaStatus::InvalidMedia);
+        assert_eq!(state.play_request_id, Some(2));
+        assert_eq!((state.position_ms, state.duration_ms), (0, 0));
+
+        let state = applied(&[
+            playing(1000),
+            LibrespotEvent::Stopped {
+                play_request_id: 1,
+                track_id: "spotify:track:a".to_string(),
+            },
+        ]);
+        assert_eq!(state.player_state, PlayerState::Stopped);
+        assert_eq!(state.media_status, MediaStatus::NoMedia);
+        assert_eq!((state.position_ms, state.duration_ms), (0, 0));
+    }
+
+    #[test]
+    fn keeps_the_volume_over_a_shutdown() {
+        let state = applied(&[
+            LibrespotEvent::Connected,
+            playing(1000),
+            LibrespotEvent::VolumeSet { volume: 32768 },
+            LibrespotEvent::ShuffleChanged { shuffle: true },
+            LibrespotEvent::Shutdown,
+        ]);
+        assert_eq!(
+            state,
+            StateSnapshot {
+                volume: 32768,
+                ..StateSnapshot::default()
+            }
+        );
+    }
+
+    #[test]
+    fn follows_the_connection() {
+        let state = applied(&[LibrespotEvent::Connecting]);
+        assert_eq!(state.connection_status, ConnectionStatus::Connecting);
+
+        let state = applied(&[LibrespotEvent::Connecting, LibrespotEvent::Connected]);
+        assert_eq!(state.connection_status, ConnectionStatus::Connected);
+
+        let state = applied(&[
+            LibrespotEvent::Connected,
+            LibrespotEvent::ConnectionError {
+                message: "reset".to_string(),
+            },
+        ]);
+        assert_eq!(state.connection_status, ConnectionStatus::Disconnected);
+
+        let state = applied(&[LibrespotEvent::Connected, LibrespotEvent::StartReconnect]);
+        assert_eq!(state.connection_status, ConnectionStatus::Connecting);
+
+        let state = applied(&[LibrespotEvent::WaitingForDiscovery]);
+        assert_eq!(
+            state.connection_status,
+            ConnectionStatus::WaitingForDiscovery
+        );
+
+        let state = applied(&[
+            LibrespotEvent::Connected,
+            playing(1000),
+            LibrespotEvent::Panic {
+                message: "boom".to_string(),
+            },
+        ]);
+        assert_eq!(state.connection_status, ConnectionStatus::Crashed);
+        assert_eq!(state.player_state, PlayerState::Stopped);
+    }
+
+    #[test]
+    fn failed_logins_disconnect() {
+        for err in [
+            LibrespotError::BadCredentials,
+            LibrespotError::PremiumRequired,
+            LibrespotError::RateLimited("TryAnotherAP".to_string()),
+        ] {
+            let state = applied(&[LibrespotEvent::Connecting, LibrespotEvent::Error { err }]);
+            assert_eq!(state.connection_status, ConnectionStatus::Disconnected);
+        }
+
+        let state = applied(&[
+            LibrespotEvent::Connected,
+            LibrespotEvent::Error {
+                err: LibrespotError::OAuth("denied".to_string()),
+            },
+        ]);
+        assert_eq!(state.connection_status, ConnectionStatus::Connected);
+    }
+
+    fn paused(position_ms: u32) -> LibrespotEvent {
+        LibrespotEvent::Paused {
+            play_request_id: 1,
+            track_id: "spotify:track:a".to_string(),
+            position_ms,
+            duration_ms: 180_000,
+        }
+    }
+
+    #[test]
+    fn advances_while_playing() {
+        let start = Instant::now();
+        let mut state = StateSnapshot::default();
+        state.apply_at(&playing(1000), start);
+        assert_eq!(state.position_at(start), 1000);
+        assert_eq!(state.position_at(start + Duration::from_millis(2500)), 3500);
+        // a clock that went backwards doesn't rewind
+        assert_eq!(state.position_at(start - Duration::from_secs(1)), 1000);
+    }
+
+    #[test]
+    fn stops_at_the_duration() {
+        let start = Instant::now();
+        let mut state = StateSnapshot::default();
+        state.apply_at(&playing(179_000), start);
+        assert_eq!(state.position_at(start + Duration::from_secs(5)), 180_000);
+    }
+
+    #[test]
+    fn freezes_while_paused_or_loading() {
+        let start = Instant::now();
+        let later = start + Duration::from_secs(10);
+        let mut state = StateSnapshot::default();
+        state.apply_at(&playing(1000), start);
+        state.apply_at(&paused(2000), start + Duration::from_secs(1));
+        assert_eq!(state.position_at(later), 2000);
+
+        state.apply_at(
+            &LibrespotEvent::Loading {
+                play_request_id: 2,
+                track_id: "spotify:track:b".to_string(),
+                position_ms: 500,
+            },
+            start,
+        );
+        assert_eq!(state.position_at(later), 500);
+    }
+
+    #[test]
+    fn seeking_restarts_from_the_new_position() {
+        let start = Instant::now();
+        let seeked = start + Duration::from_secs(10);
+        let mut state = StateSnapshot::default();
+        state.apply_at(&playing(1000), start);
+        // the player reports the seek as playing from the new position
+        state.apply_at(&playing(60_000), seeked);
+        assert_eq!(state.position_at(seeked), 60_000);
+        assert_eq!(
+            state.position_at(seeked + Duration::from_millis(1500)),
+            61_500
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Keeps the access token fresh
+//!
+//! A new token is fetched shortly before the current one expires. Failed requests are retried with
+//! an increasing delay. Refresh requests that arrive while a token is fetched are coalesced.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::StreamExt;
+use librespot_core::keymaster::{get_token, Token};
+use librespot_core::session::Session;
+use log::{info, warn};
+
+use crate::player::events::{LibrespotEvent, LibrespotEventListenerRef};
+use crate::player::secret::SecretString;
+use crate::player::{CLIENT_ID, SCOPES};
+
+/// Time before expiry at which a token is refreshed
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+const MIN_REFRESH_DELAY: Duration = Duration::from_secs(30);
+
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Access token for the Web API
+#[derive(Clone, Debug)]
+pub struct AccessToken {
+    pub access_token: SecretString,
+    /// Seconds until the token expires
+    pub expires_in: u32,
+}
+
+impl From<Token> for AccessToken {
+    fn from(token: Token) -> Self {
+        let Token {
+            access_token,
+            expires_in,
+            ..
+        } = token;
+        Self {
+            access_token: SecretString::new(access_token),
+            expires_in,
+        }
+    }
+}
+
+pub async fn fetch_token(session: &Session) -> Result<AccessToken, String> {
+    get_token(session, CLIENT_ID, SCOPES)
+        .await
+        .map(AccessToken::from)
+        .map_err(|err| format!("{:?}", err))
+}
+
+pub type TokenFuture<'a> = Pin<Box<dyn Future<Output = Result<AccessToken, String>> + Send + 'a>>;
+
+/// Where tokens come from, replaceable for tests
+pub trait TokenSource: Send {
+    /// No more tokens can be fetched, the refresher ends
+    fn is_closed(&self) -> bool;
+
+    fn fetch(&self) -> TokenFuture<'_>;
+}
+
+impl TokenSource for Session {
+    fn is_closed(&self) -> bool {
+        self.is_invalid()
+    }
+
+    fn fetch(&self) -> TokenFuture<'_> {
+        Box::pin(fetch_token(self))
+    }
+}
+
+/// Delays until the next token request
+struct RefreshSchedule {
+    retry_delay: Duration,
+}
+
+impl RefreshSchedule {
+    fn new() -> Self {
+        Self {
+            retry_delay: MIN_RETRY_DELAY,
+        }
+    }
+
+    /// Delay after the result of a request, growing while requests fail
+    fn next_delay(&mut self, token: &Result<AccessToken, String>) -> Duration {
+        match token {
+            Ok(token) => {
+                self.retry_delay = MIN_RETRY_DELAY;
+                Duration::from_secs(u64::from(token.expires_in))
+                    .saturating_sub(REFRESH_MARGIN)
+                    .max(MIN_REFRESH_DELAY)
+            }
+            Err(_) => {
+                let delay = self.retry_delay;
+                self.retry_delay = (self.retry_delay * 2).min(MAX_RETRY_DELAY);
+                delay
+            }
+        }
+    }
+}
+
+/// Refresh the token of `source` until it is closed or the sender of `requests` goes away
+///
+/// `token` is the result of the last token request.
+pub async fn run_token_refresher<S: TokenSource>(
+    source: S,
+    listener: LibrespotEventListenerRef,
+    mut requests: UnboundedReceiver<()>,
+    mut token: Result<AccessToken, String>,
+) {
+    let mut schedule = RefreshSchedule::new();
+    loop {
+        let delay = schedule.next_delay(&token);
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => info!("Refreshing access token ..."),
+            request = requests.next() => {
+                if request.is_none() {
+                    return;
+                }
+                info!("Refreshing access token on request ...");
+            }
+        }
+        if source.is_closed() {
+            return;
+        }
+
+        token = source.fetch().await;
+        if let Err(err) = &token {
+            warn!("Failed to refresh access token: {}", err);
+        }
+
+        // requests made while fetching are answered by this token
+        while let Ok(Some(())) = requests.try_next() {}
+
+        listener.notify(LibrespotEvent::TokenChanged {
+            token: token.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use futures::channel::mpsc::unbounded;
+    use tokio::sync::{mpsc, Notify};
+
+    use super::*;
+    use crate::player::events::LibrespotEventListener;
+
+    fn token(expires_in: u32) -> Result<AccessToken, String> {
+        Ok(AccessToken {
+            access_token: SecretString::from("token"),
+            expires_in,
+        })
+    }
+
+    #[test]
+    fn refreshes_before_expiry() {
+        let mut schedule = RefreshSchedule::new();
+        assert_eq!(
+            schedule.next_delay(&token(3600)),
+            Duration::from_secs(3600) - REFRESH_MARGIN
+        );
+        // short lived tokens are not refreshed in a tight loop
+        assert_eq!(schedule.next_delay(&token(310)), MIN_REFRESH_DELAY);
+        assert_eq!(schedule.next_delay(&token(0)), MIN_REFRESH_DELAY);
+    }
+
+    #[test]
+    fn retries_with_growing_delay() {
+        let mut schedule = RefreshSchedule::new();
+        let failed = Err("failed".to_string());
+        let delays: Vec<u64> = (0..10)
+            .map(|_| schedule.next_delay(&failed).as_secs())
+            .collect();
+        assert_eq!(delays, vec![2, 4, 8, 16, 32, 64, 128, 256, 300, 300]);
+
+        schedule.next_delay(&token(3600));
+        assert_eq!(schedule.next_delay(&failed), MIN_RETRY_DELAY);
+    }
+
+    /// Hands out tokens only when released, counting the requests
+    #[derive(Clone, Default)]
+    struct HeldSource {
+        fetches: Arc<AtomicUsize>,
+        release: Arc<Notify>,
+        closed: Arc<AtomicBool>,
+    }
+
+    impl TokenSource for HeldSource {
+        fn is_closed(&self) -> bool {
+            self.closed.load(Ordering::SeqCst)
+        }
+
+        fn fetch(&self) -> TokenFuture<'_> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                self.release.notified().await;
+                token(3600)
+            })
+        }
+    }
+
+    struct TokenListener(Mutex<mpsc::UnboundedSender<()>>);
+
+    impl LibrespotEventListener for TokenListener {
+        fn notify(&self, evt: LibrespotEvent) {
+            if let LibrespotEvent::TokenChanged { .. } = evt {
+                let _ = self.0.lock().unwrap().send(());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn coalesces_requests_during_a_fetch() {
+        let source = HeldSource::default();
+        let (changed_tx, mut changed) = mpsc::unbounded_channel();
+        let listener = Arc::new(TokenListener(Mutex::new(changed_tx)));
+        let (requests, requests_rx) = unbounded();
+        let refresher = tokio::spawn(run_token_refresher(
+            source.clone(),
+            listener,
+            requests_rx,
+            token(3600),
+        ));
+
+        requests.unbounded_send(()).unwrap();
+        while source.fetches.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        for _ in 0..3 {
+            requests.unbounded_send(()).unwrap();
+        }
+        source.release.notify_one();
+        changed.recv().await.unwrap();
+
+        // the requests made meanwhile got the new token, no second fetch starts
+        let second = tokio::time::timeout(Duration::from_millis(100), changed.recv()).await;
+        assert!(second.is_err());
+        assert_eq!(source.fetches.load(Ordering::SeqCst), 1);
+
+        source.closed.store(true, Ordering::SeqCst);
+        requests.unbounded_send(()).unwrap();
+        refresher.await.unwrap();
+        assert_eq!(source.fetches.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,18 @@
+//! Conversion between volume in percent (as used by the UI) and the raw volume used by librespot
+
+const MAX_RAW_VOLUME: u32 = 0xFFFF;
+
+/// Converts a volume in the range 0-100 to librespot's range 0-0xFFFF.
+///
+/// Values above 100 are clamped.
+#[must_use]
+pub fn percent_to_raw(percent: u16) -> u16 {
+    let percent = u32::from(percent.min(100));
+    (percent * MAX_RAW_VOLUME / 100) as u16
+}
+
+/// Converts a librespot volume in the range 0-0xFFFF to the range 0-100.
+#[must_use]
+pub fn raw_to_percent(raw: u16) -> u16 {
+    ((u32::from(raw) * 100 + MAX_RAW_VOLUME / 2) / MAX_RAW_VOLUME) as u16
+}
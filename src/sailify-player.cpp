@@ -3,6 +3,8 @@
 #include <QLoggingCategory>
 #include <QDateTime>
 
+#include <vector>
+
 namespace Sailify {
 
 namespace {
@@ -13,6 +15,16 @@ static inline QString toQString(SailifyStringView ffi_view) {
     return QString::fromUtf8(ffi_view.ptr, ffi_view.len);
 }
 
+// takes the ownership of the string
+static inline QString toQString(SailifyString* ffi_string) {
+    if (ffi_string == nullptr) {
+        return QString();
+    }
+    QString result = toQString(sailify_string_get(ffi_string));
+    sailify_string_delete(ffi_string);
+    return result;
+}
+
 static inline SailifyStringView toFfi(const QByteArray& utf8) {
     return {
         .ptr = utf8.data(),
@@ -24,14 +36,7 @@ SailifyPlayer::SailifyPlayer() {
     auto* callback = new SailifyPlayerCallback();
     auto ffiCallback = callback->createFfiCallback();
     m_player = ::sailify_player_new(&ffiCallback);
-
-    m_positionTimer.setInterval(1000);
-    connect(
-        &m_positionTimer, &QTimer::timeout,
-        this, [this]() {
-            emit positionChanged(
-                    m_positionMs + m_positionElapsedTimer.elapsed());
-        });
+    sailify_player_set_position_interval_ms(m_player, 1000);
 
     connect(
         callback, &SailifyPlayerCallback::stopped,
@@ -60,6 +65,9 @@ SailifyPlayer::SailifyPlayer() {
     connect(
         callback, &SailifyPlayerCallback::connected,
         this, &SailifyPlayer::onConnected);
+    connect(
+        callback, &SailifyPlayerCallback::loginProgress,
+        this, &SailifyPlayer::onLoginProgress);
     connect(
         callback, &SailifyPlayerCallback::error,
         this, &SailifyPlayer::onError);
@@ -69,9 +77,61 @@ SailifyPlayer::SailifyPlayer() {
     connect(
         callback, &SailifyPlayerCallback::startReconnect,
         this, &SailifyPlayer::onStartReconnect);
+    connect(
+        callback, &SailifyPlayerCallback::waitingForDiscovery,
+        this, &SailifyPlayer::onWaitingForDiscovery);
     connect(
         callback, &SailifyPlayerCallback::tokenChanged,
         this, &SailifyPlayer::onTokenChanged);
+    connect(
+        callback, &SailifyPlayerCallback::shuffleChanged,
+        this, &SailifyPlayer::onShuffleChanged);
+    connect(
+        callback, &SailifyPlayerCallback::repeatChanged,
+        this, &SailifyPlayer::onRepeatChanged);
+    connect(
+        callback, &SailifyPlayerCallback::settingsApplied,
+        this, &SailifyPlayer::onSettingsApplied);
+    connect(
+        callback, &SailifyPlayerCallback::reconnectScheduled,
+        this, [this](quint32 attempt, quint64 delayMs) {
+            qCInfo(logger) << "Reconnect attempt" << attempt << "in" << delayMs << "ms";
+            emit reconnectScheduled(attempt, delayMs);
+        });
+    connect(
+        callback, &SailifyPlayerCallback::stateChanged,
+        this, &SailifyPlayer::stateChanged);
+    connect(
+        callback, &SailifyPlayerCallback::position,
+        this, &SailifyPlayer::positionChanged);
+    connect(
+        callback, &SailifyPlayerCallback::oauthCompleted,
+        this, [this]() {
+            qCInfo(logger) << "OAuth login completed";
+            emit oauthLoginCompleted();
+        });
+    connect(
+        callback, &SailifyPlayerCallback::remoteLogin,
+        this, [this](const QString& username) {
+            qCInfo(logger) << "Login handed over by a Connect app";
+            emit remoteLogin(username);
+        });
+    // queued, so that results of failed calls arrive after the call returned the request ID
+    connect(
+        callback, &SailifyPlayerCallback::commandResult,
+        this, [this](quint64 requestId, const QString& command, SailifyCommandStatus status, const QString& message) {
+            auto value = static_cast<CommandStatus>(status);
+            if (value == CommandCompleted) {
+                qCDebug(logger) << "Command" << command << requestId << "completed";
+            } else {
+                qCWarning(logger) << "Command" << command << requestId << "failed:" << message;
+            }
+            emit commandResult(requestId, command, value, message);
+        },
+        Qt::QueuedConnection);
+    connect(
+        callback, &SailifyPlayerCallback::accountInfo,
+        this, &SailifyPlayer::onAccountInfo);
 }
 
 SailifyPlayer::~SailifyPlayer() {
@@ -89,12 +149,77 @@ void SailifyPlayer::setUsername(const QString& value) {
 }
 
 QString SailifyPlayer::password() const {
-    return toQString(sailify_player_get_password(m_player));
+    // write-only, the player does not hand out the password
+    return QString();
 }
 
 void SailifyPlayer::setPassword(const QString& value) {
     QByteArray utf8 = value.toUtf8();
     sailify_player_set_password(m_player, toFfi(utf8));
+    utf8.fill('\0');
+}
+
+QString SailifyPlayer::proxy() const {
+    return toQString(sailify_player_get_proxy(m_player));
+}
+
+void SailifyPlayer::setProxy(const QString& value) {
+    QByteArray utf8 = value.toUtf8();
+    sailify_player_set_proxy(m_player, toFfi(utf8));
+    updateNeedsRestart();
+}
+
+SailifyPlayer::Bitrate SailifyPlayer::bitrate() const {
+    return static_cast<Bitrate>(sailify_player_get_bitrate(m_player));
+}
+
+void SailifyPlayer::setBitrate(Bitrate value) {
+    sailify_player_set_bitrate(m_player, static_cast<uint32_t>(value));
+}
+
+bool SailifyPlayer::gapless() const {
+    return sailify_player_get_gapless(m_player);
+}
+
+void SailifyPlayer::setGapless(bool value) {
+    sailify_player_set_gapless(m_player, value);
+}
+
+bool SailifyPlayer::volumeNormalisation() const {
+    return sailify_player_get_volume_normalisation(m_player);
+}
+
+void SailifyPlayer::setVolumeNormalisation(bool value) {
+    sailify_player_set_volume_normalisation(m_player, value);
+}
+
+bool SailifyPlayer::autoplay() const {
+    return sailify_player_get_autoplay(m_player);
+}
+
+void SailifyPlayer::setAutoplay(bool value) {
+    sailify_player_set_autoplay(m_player, value);
+}
+
+bool SailifyPlayer::discovery() const {
+    return sailify_player_get_discovery(m_player);
+}
+
+void SailifyPlayer::setDiscovery(bool value) {
+    sailify_player_set_discovery(m_player, value);
+    updateNeedsRestart();
+}
+
+bool SailifyPlayer::needsRestart() const {
+    return m_needsRestart;
+}
+
+void SailifyPlayer::updateNeedsRestart() {
+    bool value = sailify_player_needs_restart(m_player);
+    if (value != m_needsRestart) {
+        m_needsRestart = value;
+        emit needsRestartChanged(value);
+    }
 }
 
 bool SailifyPlayer::isActive() const {
@@ -117,6 +242,10 @@ SailifyPlayer::ConnectionStatus SailifyPlayer::connectionStatus() const {
     return m_connectionStatus;
 }
 
+SailifyPlayer::LoginStage SailifyPlayer::loginStage() const {
+    return m_loginStage;
+}
+
 QString SailifyPlayer::trackUri() const {
     return m_trackId;
 }
@@ -126,17 +255,40 @@ SailifyPlayer::PlaybackState SailifyPlayer::playbackState() const {
 }
 
 qint32 SailifyPlayer::position() const {
-    if (m_positionElapsedTimer.isValid()) {
-        return m_positionMs + m_positionElapsedTimer.elapsed();
-    } else {
-        return m_positionMs;
-    }
+    return sailify_player_current_position_ms(m_player);
 }
 
 qint32 SailifyPlayer::duration() const {
     return m_durationMs;
 }
 
+quint16 SailifyPlayer::volume() const {
+    return m_volume;
+}
+
+void SailifyPlayer::setVolume(quint16 value) {
+    qCInfo(logger) << "Requested volume" << value;
+    sailify_player_set_volume(m_player, value);
+}
+
+bool SailifyPlayer::shuffle() const {
+    return m_shuffle;
+}
+
+void SailifyPlayer::setShuffle(bool value) {
+    qCInfo(logger) << "Requested shuffle" << value;
+    sailify_player_set_shuffle(m_player, value);
+}
+
+SailifyPlayer::Repeat SailifyPlayer::repeat() const {
+    return m_repeat;
+}
+
+void SailifyPlayer::setRepeat(Repeat value) {
+    qCInfo(logger) << "Requested repeat" << value;
+    sailify_player_set_repeat(m_player, static_cast<uint32_t>(value));
+}
+
 QString SailifyPlayer::accessToken() const {
     return m_accessToken;
 }
@@ -153,6 +305,36 @@ QString SailifyPlayer::deviceName() const {
     return toQString(sailify_player_get_device_name(m_player));
 }
 
+QStringList SailifyPlayer::profiles() const {
+    return toQString(sailify_player_get_profiles(m_player)).split(',', QString::SkipEmptyParts);
+}
+
+QString SailifyPlayer::activeProfile() const {
+    return toQString(sailify_player_get_active_profile(m_player));
+}
+
+QString SailifyPlayer::accountName() const {
+    return m_accountName;
+}
+
+QString SailifyPlayer::country() const {
+    return m_country;
+}
+
+SailifyPlayer::Product SailifyPlayer::product() const {
+    return m_product;
+}
+
+quint64 SailifyPlayer::subscribe(SailifyPlayerCallback* callback, quint32 categories) {
+    // the player owns the callback from now on and deletes it after unsubscribing
+    auto ffiCallback = callback->createFfiCallback();
+    return sailify_player_subscribe(m_player, &ffiCallback, categories);
+}
+
+bool SailifyPlayer::unsubscribe(quint64 subscription) {
+    return sailify_player_unsubscribe(m_player, subscription);
+}
+
 void SailifyPlayer::refreshAccessToken() {
     qCInfo(logger) << "Requested new access token";
     sailify_player_refresh_access_token(m_player);
@@ -161,36 +343,134 @@ void SailifyPlayer::refreshAccessToken() {
 void SailifyPlayer::start() {
     qCInfo(logger) << "Requested start";
     sailify_player_start(m_player);
+    updateNeedsRestart();
 }
 
 void SailifyPlayer::stop() {
     qCInfo(logger) << "Requested stop";
     sailify_player_stop(m_player);
+    updateNeedsRestart();
 }
 
 void SailifyPlayer::logout() {
     qCInfo(logger) << "Requested logout";
     sailify_player_logout(m_player);
+
+    m_accountName.clear();
+    m_country.clear();
+    m_product = UnknownProduct;
+    emit accountInfoChanged();
+}
+
+bool SailifyPlayer::createProfile(const QString& name) {
+    qCInfo(logger) << "Requested creating profile" << name;
+    QByteArray utf8 = name.toUtf8();
+    if (!sailify_player_create_profile(m_player, toFfi(utf8))) {
+        return false;
+    }
+    emit profilesChanged();
+    return true;
 }
 
-void SailifyPlayer::play() {
+bool SailifyPlayer::deleteProfile(const QString& name) {
+    qCInfo(logger) << "Requested deleting profile" << name;
+    QByteArray utf8 = name.toUtf8();
+    if (!sailify_player_delete_profile(m_player, toFfi(utf8))) {
+        return false;
+    }
+    emit profilesChanged();
+    return true;
+}
+
+bool SailifyPlayer::switchProfile(const QString& name) {
+    qCInfo(logger) << "Requested switching to profile" << name;
+    QByteArray utf8 = name.toUtf8();
+    bool result = sailify_player_switch_profile(m_player, toFfi(utf8));
+    emit activeProfileChanged();
+    updateNeedsRestart();
+    return result;
+}
+
+QString SailifyPlayer::startOAuthLogin() {
+    qCInfo(logger) << "Requested OAuth login";
+    return toQString(sailify_player_start_oauth_login(m_player));
+}
+
+void SailifyPlayer::cancelOAuthLogin() {
+    qCInfo(logger) << "Requested cancelling OAuth login";
+    sailify_player_cancel_oauth_login(m_player);
+}
+
+bool SailifyPlayer::applySettings() {
+    qCInfo(logger) << "Requested applying settings";
+    bool result = sailify_player_apply_settings(m_player);
+    updateNeedsRestart();
+    return result;
+}
+
+bool SailifyPlayer::saveSettings() {
+    qCInfo(logger) << "Requested saving settings";
+    return sailify_player_save_settings(m_player);
+}
+
+void SailifyPlayer::networkChanged(bool online, bool metered) {
+    qCInfo(logger) << "Network changed, online:" << online << "metered:" << metered;
+    sailify_player_network_changed(m_player, online, metered);
+}
+
+quint64 SailifyPlayer::play() {
     qCInfo(logger) << "Requested play";
-    sailify_player_play(m_player);
+    return sailify_player_play(m_player);
 }
 
-void SailifyPlayer::pause() {
+quint64 SailifyPlayer::pause() {
     qCInfo(logger) << "Requested pause";
-    sailify_player_pause(m_player);
+    return sailify_player_pause(m_player);
 }
 
-void SailifyPlayer::next() {
+quint64 SailifyPlayer::next() {
     qCInfo(logger) << "Requested next";
-    sailify_player_next(m_player);
+    return sailify_player_next(m_player);
 }
 
-void SailifyPlayer::previous() {
+quint64 SailifyPlayer::previous() {
     qCInfo(logger) << "Requested previous";
-    sailify_player_previous(m_player);
+    return sailify_player_previous(m_player);
+}
+
+quint64 SailifyPlayer::seek(quint32 positionMs) {
+    qCInfo(logger) << "Requested seek to" << positionMs;
+    return sailify_player_seek(m_player, positionMs);
+}
+
+quint64 SailifyPlayer::load(
+        const QString& contextUri, const QStringList& trackUris,
+        quint32 startIndex, quint32 positionMs, bool shuffle) {
+    qCInfo(logger) << "Requested load of" << contextUri;
+
+    QByteArray contextUriUtf8 = contextUri.toUtf8();
+    std::vector<QByteArray> trackUrisUtf8;
+    std::vector<SailifyStringView> trackUriViews;
+    trackUrisUtf8.reserve(trackUris.size());
+    trackUriViews.reserve(trackUris.size());
+    for (const QString& trackUri : trackUris) {
+        trackUrisUtf8.push_back(trackUri.toUtf8());
+        trackUriViews.push_back(toFfi(trackUrisUtf8.back()));
+    }
+
+    return sailify_player_load(
+        m_player, toFfi(contextUriUtf8), trackUriViews.data(), trackUriViews.size(),
+        startIndex, positionMs, shuffle);
+}
+
+quint64 SailifyPlayer::volumeUp() {
+    qCInfo(logger) << "Requested volume up";
+    return sailify_player_volume_up(m_player);
+}
+
+quint64 SailifyPlayer::volumeDown() {
+    qCInfo(logger) << "Requested volume down";
+    return sailify_player_volume_down(m_player);
 }
 
 void SailifyPlayer::updatePosition() {
@@ -212,14 +492,6 @@ void SailifyPlayer::setPlayerStatus(
     m_mediaStatus = mediaStatus;
     m_playbackState = playbackState;
 
-    if (m_playbackState == Playing && m_positionMs >= 0) {
-        m_positionElapsedTimer.restart();
-        m_positionTimer.start();
-    } else {
-        m_positionElapsedTimer.invalidate();
-        m_positionTimer.stop();
-    }
-
     if (changedMediaStatus) {
         emit mediaStatusChanged(m_mediaStatus);
     }
@@ -280,7 +552,7 @@ void SailifyPlayer::onVolumeChanged(quint16 value) {
     if (value != m_volume) {
         qCDebug(logger) << "Volume changed:" << value;
         m_volume = value;
-        emit onVolumeChanged(value);
+        emit volumeChanged(value);
     }
 }
 
@@ -302,6 +574,15 @@ void SailifyPlayer::onConnected() {
     setConnectionStatus(Connected);
 }
 
+void SailifyPlayer::onLoginProgress(::LoginStage stage) {
+    auto value = static_cast<LoginStage>(stage);
+    qCInfo(logger) << "Login stage:" << value;
+    if (m_loginStage != value) {
+        m_loginStage = value;
+        emit loginStageChanged(value);
+    }
+}
+
 void SailifyPlayer::onError(SailifyErrorKind kind, const QString& message) {
     switch (kind) {
         case SailifyErrorKind::MissingCredentials: return setError(MissingCredentials, message);
@@ -309,6 +590,14 @@ void SailifyPlayer::onError(SailifyErrorKind kind, const QString& message) {
         case SailifyErrorKind::Io: return setError(IoError, message);
         case SailifyErrorKind::Connection: return setError(ConnectionError, message);
         case SailifyErrorKind::Panic: return setError(Panic, message);
+        case SailifyErrorKind::OAuth: return setError(OAuthError, message);
+        case SailifyErrorKind::PremiumRequired: return setError(PremiumRequired, message);
+        case SailifyErrorKind::BadCredentials: return setError(BadCredentials, message);
+        case SailifyErrorKind::NetworkUnreachable: return setError(NetworkUnreachable, message);
+        case SailifyErrorKind::AccessPointRefused: return setError(AccessPointRefused, message);
+        case SailifyErrorKind::Timeout: return setError(Timeout, message);
+        case SailifyErrorKind::RateLimited: return setError(RateLimited, message);
+        case SailifyErrorKind::AccountRestricted: return setError(AccountRestricted, message);
         case SailifyErrorKind::Token:
             qCCritical(logger) << "Access token refresh error:" << message;
             return emit accessTokenRefreshFailed(message);
@@ -326,6 +615,7 @@ void SailifyPlayer::setError(ErrorKind kind, const QString& message) {
 void SailifyPlayer::onShutdown() {
     qCInfo(logger) << "Player shutdown";
     setConnectionStatus(Disconnected);
+    updateNeedsRestart();
 }
 
 void SailifyPlayer::onStartReconnect() {
@@ -333,6 +623,11 @@ void SailifyPlayer::onStartReconnect() {
     setConnectionStatus(Connecting);
 }
 
+void SailifyPlayer::onWaitingForDiscovery() {
+    qCInfo(logger) << "Waiting for a Connect app to hand over a login";
+    setConnectionStatus(WaitingForDiscovery);
+}
+
 void SailifyPlayer::onTokenChanged(const QString& accessToken, quint32 expiresIn) {
     qCInfo(logger) << "Access token changed - expiresIn:" << expiresIn;
 
@@ -341,6 +636,39 @@ void SailifyPlayer::onTokenChanged(const QString& accessToken, quint32 expiresIn
     emit accessTokenChanged(m_accessToken);
 }
 
+void SailifyPlayer::onShuffleChanged(bool shuffle) {
+    if (shuffle != m_shuffle) {
+        qCDebug(logger) << "Shuffle changed:" << shuffle;
+        m_shuffle = shuffle;
+        emit shuffleChanged(shuffle);
+    }
+}
+
+void SailifyPlayer::onRepeatChanged(RepeatMode repeat) {
+    auto value = static_cast<Repeat>(repeat);
+    if (value != m_repeat) {
+        qCDebug(logger) << "Repeat changed:" << value;
+        m_repeat = value;
+        emit repeatChanged(value);
+    }
+}
+
+void SailifyPlayer::onSettingsApplied(const QString& applied, const QString& needsRestart) {
+    auto appliedList = applied.split(',', QString::SkipEmptyParts);
+    auto needsRestartList = needsRestart.split(',', QString::SkipEmptyParts);
+    qCDebug(logger) << "Settings applied:" << appliedList << "needs restart:" << needsRestartList;
+    emit settingsApplied(appliedList, needsRestartList);
+}
+
+void SailifyPlayer::onAccountInfo(
+        const QString& username, const QString& displayName, const QString& country, AccountProduct product) {
+    qCInfo(logger) << "Account info - country:" << country << "product:" << product;
+    m_accountName = displayName.isEmpty() ? username : displayName;
+    m_country = country;
+    m_product = static_cast<Product>(product);
+    emit accountInfoChanged();
+}
+
 ::SailifyCallback SailifyPlayerCallback::createFfiCallback() {
     SailifyCallback callback = {
         .user_data = this,
@@ -356,7 +684,19 @@ void SailifyPlayer::onTokenChanged(const QString& accessToken, quint32 expiresIn
         .error = SailifyPlayerCallback::onError,
         .shutdown = SailifyPlayerCallback::onShutdown,
         .start_reconnect = SailifyPlayerCallback::onStartReconnect,
+        .waiting_for_discovery = SailifyPlayerCallback::onWaitingForDiscovery,
         .token_changed = SailifyPlayerCallback::onTokenChanged,
+        .shuffle_changed = SailifyPlayerCallback::onShuffleChanged,
+        .repeat_changed = SailifyPlayerCallback::onRepeatChanged,
+        .settings_applied = SailifyPlayerCallback::onSettingsApplied,
+        .reconnect_scheduled = SailifyPlayerCallback::onReconnectScheduled,
+        .state_changed = SailifyPlayerCallback::onStateChanged,
+        .position = SailifyPlayerCallback::onPosition,
+        .oauth_completed = SailifyPlayerCallback::onOAuthCompleted,
+        .remote_login = SailifyPlayerCallback::onRemoteLogin,
+        .account_info = SailifyPlayerCallback::onAccountInfo,
+        .login_progress = SailifyPlayerCallback::onLoginProgress,
+        .command_result = SailifyPlayerCallback::onCommandResult,
         .destroy = SailifyPlayerCallback::onDestroy,
     };
     return callback;
@@ -398,6 +738,17 @@ void SailifyPlayerCallback::onConnected(void *user_data) {
     emit static_cast<SailifyPlayerCallback*>(user_data)->connected();
 }
 
+void SailifyPlayerCallback::onLoginProgress(void *user_data, ::LoginStage stage) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->loginProgress(stage);
+}
+
+void SailifyPlayerCallback::onCommandResult(
+        void *user_data, uint64_t request_id, SailifyStringView command, SailifyCommandStatus status,
+        SailifyStringView message) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->commandResult(
+        request_id, toQString(command), status, toQString(message));
+}
+
 void SailifyPlayerCallback::onError(void *user_data, SailifyErrorKind kind, SailifyStringView message) {
     emit static_cast<SailifyPlayerCallback*>(user_data)->error(kind, toQString(message));
 }
@@ -410,10 +761,54 @@ void SailifyPlayerCallback::onStartReconnect(void *user_data) {
     emit static_cast<SailifyPlayerCallback*>(user_data)->startReconnect();
 }
 
+void SailifyPlayerCallback::onWaitingForDiscovery(void *user_data) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->waitingForDiscovery();
+}
+
 void SailifyPlayerCallback::onTokenChanged(void *user_data, SailifyStringView access_token, uint32_t expires_in) {
     emit static_cast<SailifyPlayerCallback*>(user_data)->tokenChanged(toQString(access_token), expires_in);
 }
 
+void SailifyPlayerCallback::onShuffleChanged(void *user_data, bool shuffle) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->shuffleChanged(shuffle);
+}
+
+void SailifyPlayerCallback::onRepeatChanged(void *user_data, RepeatMode repeat) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->repeatChanged(repeat);
+}
+
+void SailifyPlayerCallback::onSettingsApplied(void *user_data, SailifyStringView applied, SailifyStringView needs_restart) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->settingsApplied(toQString(applied), toQString(needs_restart));
+}
+
+void SailifyPlayerCallback::onReconnectScheduled(void *user_data, uint32_t attempt, uint64_t delay_ms) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->reconnectScheduled(attempt, delay_ms);
+}
+
+void SailifyPlayerCallback::onStateChanged(void *user_data, const SailifyPlayerState *) {
+    // the state is only valid during the call, receivers query it with sailify_player_get_state
+    emit static_cast<SailifyPlayerCallback*>(user_data)->stateChanged();
+}
+
+void SailifyPlayerCallback::onPosition(void *user_data, uint32_t position_ms) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->position(position_ms);
+}
+
+void SailifyPlayerCallback::onOAuthCompleted(void *user_data) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->oauthCompleted();
+}
+
+void SailifyPlayerCallback::onRemoteLogin(void *user_data, SailifyStringView username) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->remoteLogin(toQString(username));
+}
+
+void SailifyPlayerCallback::onAccountInfo(
+        void *user_data, SailifyStringView username, SailifyStringView display_name, SailifyStringView country,
+        AccountProduct product) {
+    emit static_cast<SailifyPlayerCallback*>(user_data)->accountInfo(
+        toQString(username), toQString(display_name), toQString(country), product);
+}
+
 void SailifyPlayerCallback::onDestroy(void *user_data) {
     delete static_cast<SailifyPlayerCallback*>(user_data);
 }
@@ -3,30 +3,47 @@
 #include <QObject>
 #include <QString>
 #include <QString>
-#include <QTimer>
-#include <QElapsedTimer>
+#include <QStringList>
 
 #include <sailifyplayer.h>
 
 namespace Sailify {
 
+class SailifyPlayerCallback;
+
 class SailifyPlayer : public QObject {
     Q_OBJECT
     Q_PROPERTY(QString username READ username WRITE setUsername)
     Q_PROPERTY(QString password READ password WRITE setPassword)
+    Q_PROPERTY(QString proxy READ proxy WRITE setProxy)
+    Q_PROPERTY(Bitrate bitrate READ bitrate WRITE setBitrate)
+    Q_PROPERTY(bool gapless READ gapless WRITE setGapless)
+    Q_PROPERTY(bool volumeNormalisation READ volumeNormalisation WRITE setVolumeNormalisation)
+    Q_PROPERTY(bool autoplay READ autoplay WRITE setAutoplay)
+    Q_PROPERTY(bool discovery READ discovery WRITE setDiscovery)
+    Q_PROPERTY(bool needsRestart READ needsRestart NOTIFY needsRestartChanged)
     Q_PROPERTY(bool active READ isActive NOTIFY activeChanged)
     Q_PROPERTY(QString errorString READ errorString NOTIFY errorOccurred)
     Q_PROPERTY(ErrorKind errorKind READ errorKind NOTIFY errorOccurred)
     Q_PROPERTY(MediaStatus mediaStatus READ mediaStatus NOTIFY mediaStatusChanged)
     Q_PROPERTY(ConnectionStatus connectionStatus READ connectionStatus NOTIFY connectionStatusChanged)
+    Q_PROPERTY(LoginStage loginStage READ loginStage NOTIFY loginStageChanged)
     Q_PROPERTY(QString trackUri READ trackUri NOTIFY trackUriChanged)
     Q_PROPERTY(PlaybackState playbackState READ playbackState NOTIFY playbackStateChanged)
     Q_PROPERTY(quint32 position READ position NOTIFY positionChanged)
     Q_PROPERTY(quint32 duration READ duration NOTIFY durationChanged)
+    Q_PROPERTY(quint16 volume READ volume WRITE setVolume NOTIFY volumeChanged)
+    Q_PROPERTY(bool shuffle READ shuffle WRITE setShuffle NOTIFY shuffleChanged)
+    Q_PROPERTY(Repeat repeat READ repeat WRITE setRepeat NOTIFY repeatChanged)
     Q_PROPERTY(QString accessToken READ accessToken NOTIFY accessTokenChanged)
     Q_PROPERTY(qlonglong accessTokenExpiresAt READ accessTokenExpiresAt)
     Q_PROPERTY(QString deviceId READ deviceId CONSTANT)
-    Q_PROPERTY(QString deviceName READ deviceName CONSTANT)
+    Q_PROPERTY(QString deviceName READ deviceName NOTIFY activeProfileChanged)
+    Q_PROPERTY(QStringList profiles READ profiles NOTIFY profilesChanged)
+    Q_PROPERTY(QString activeProfile READ activeProfile NOTIFY activeProfileChanged)
+    Q_PROPERTY(QString accountName READ accountName NOTIFY accountInfoChanged)
+    Q_PROPERTY(QString country READ country NOTIFY accountInfoChanged)
+    Q_PROPERTY(Product product READ product NOTIFY accountInfoChanged)
 public:
     enum MediaStatus {
         NoMedia = 0,
@@ -45,6 +62,7 @@ public:
         Disconnected = 0,
         Connecting = 1,
         Connected = 2,
+        WaitingForDiscovery = 3,
     };
     Q_ENUM(ConnectionStatus)
 
@@ -55,6 +73,20 @@ public:
     };
     Q_ENUM(PlaybackState)
 
+    enum Repeat {
+        RepeatOff = 0,
+        RepeatContext = 1,
+        // repeating a single track is not supported by the Spirc
+    };
+    Q_ENUM(Repeat)
+
+    enum Bitrate {
+        Bitrate96 = 0,
+        Bitrate160 = 1,
+        Bitrate320 = 2,
+    };
+    Q_ENUM(Bitrate)
+
     enum ErrorKind {
         NoError,
         MissingCredentials,
@@ -62,9 +94,41 @@ public:
         IoError,
         ConnectionError,
         Panic,
+        OAuthError,
+        PremiumRequired,
+        BadCredentials,
+        NetworkUnreachable,
+        AccessPointRefused,
+        Timeout,
+        RateLimited,
+        AccountRestricted,
     };
     Q_ENUM(ErrorKind)
 
+    enum CommandStatus {
+        CommandCompleted = 0,
+        CommandNotConnected = 1,
+        CommandNotRunning = 2,
+        CommandRejected = 3,
+    };
+    Q_ENUM(CommandStatus)
+
+    enum LoginStage {
+        ResolvingAccessPoint = 0,
+        ConnectingAccessPoint = 1,
+        Authenticating = 2,
+        StartingSpirc = 3,
+        FetchingToken = 4,
+    };
+    Q_ENUM(LoginStage)
+
+    enum Product {
+        UnknownProduct = 0,
+        Free = 1,
+        Premium = 2,
+    };
+    Q_ENUM(Product)
+
     SailifyPlayer();
     ~SailifyPlayer();
 
@@ -74,6 +138,26 @@ public:
     QString password() const;
     void setPassword(const QString& value);
 
+    QString proxy() const;
+    void setProxy(const QString& value);
+
+    Bitrate bitrate() const;
+    void setBitrate(Bitrate value);
+
+    bool gapless() const;
+    void setGapless(bool value);
+
+    bool volumeNormalisation() const;
+    void setVolumeNormalisation(bool value);
+
+    bool autoplay() const;
+    void setAutoplay(bool value);
+
+    bool discovery() const;
+    void setDiscovery(bool value);
+
+    bool needsRestart() const;
+
     bool isActive() const;
 
     QString errorString() const;
@@ -81,24 +165,53 @@ public:
 
     MediaStatus mediaStatus() const;
     ConnectionStatus connectionStatus() const;
+    LoginStage loginStage() const;
     PlaybackState playbackState() const;
     QString trackUri() const;
     qint32 position() const;
     qint32 duration() const;
+    quint16 volume() const;
+    void setVolume(quint16 value);
+    bool shuffle() const;
+    void setShuffle(bool value);
+    Repeat repeat() const;
+    void setRepeat(Repeat value);
     QString accessToken() const;
     qint64 accessTokenExpiresAt() const;
     QString deviceId() const;
     QString deviceName() const;
+    QStringList profiles() const;
+    QString activeProfile() const;
+    QString accountName() const;
+    QString country() const;
+    Product product() const;
+
+    quint64 subscribe(SailifyPlayerCallback* callback, quint32 categories = ALL_EVENTS);
+    bool unsubscribe(quint64 subscription);
 
 public slots:
     void refreshAccessToken();
     void start();
     void stop();
     void logout();
-    void play();
-    void pause();
-    void next();
-    void previous();
+    bool createProfile(const QString& name);
+    bool deleteProfile(const QString& name);
+    bool switchProfile(const QString& name);
+    QString startOAuthLogin();
+    void cancelOAuthLogin();
+    bool applySettings();
+    bool saveSettings();
+    quint64 play();
+    quint64 pause();
+    quint64 next();
+    quint64 previous();
+    quint64 seek(quint32 positionMs);
+    quint64 load(
+        const QString& contextUri, const QStringList& trackUris = QStringList(),
+        quint32 startIndex = 0, quint32 positionMs = 0, bool shuffle = false);
+    quint64 volumeUp();
+    quint64 volumeDown();
+    void networkChanged(bool online, bool metered);
     void updatePosition();
 
 signals:
@@ -106,12 +219,26 @@ signals:
     void errorOccurred(ErrorKind kind, const QString& message);
     void mediaStatusChanged(MediaStatus mediaStatus);
     void connectionStatusChanged(ConnectionStatus connectionStatus);
+    void loginStageChanged(LoginStage loginStage);
+    void commandResult(quint64 requestId, const QString& command, CommandStatus status, const QString& message);
     void trackUriChanged(const QString& trackUri);
     void playbackStateChanged(PlaybackState playbackState);
     void positionChanged(qint32 position);
     void durationChanged(qint32 duration);
+    void volumeChanged(quint16 volume);
+    void shuffleChanged(bool shuffle);
+    void repeatChanged(Repeat repeat);
     void accessTokenChanged(const QString& accessToken);
     void accessTokenRefreshFailed(const QString& message);
+    void settingsApplied(const QStringList& applied, const QStringList& needsRestart);
+    void needsRestartChanged(bool needsRestart);
+    void reconnectScheduled(quint32 attempt, quint64 delayMs);
+    void stateChanged();
+    void oauthLoginCompleted();
+    void remoteLogin(const QString& username);
+    void profilesChanged();
+    void activeProfileChanged();
+    void accountInfoChanged();
 
 private:
     ::SailifyPlayer* m_player = nullptr;
@@ -124,16 +251,23 @@ private:
 
     MediaStatus m_mediaStatus = NoMedia;
     ConnectionStatus m_connectionStatus = Disconnected;
+    LoginStage m_loginStage = ResolvingAccessPoint;
     PlaybackState m_playbackState = Stopped;
 
     QString m_trackId;
 
-    QTimer m_positionTimer;
-    QElapsedTimer m_positionElapsedTimer;
     qint32 m_positionMs = 0;
     qint32 m_durationMs = 0;
 
     quint16 m_volume = 0;
+    bool m_shuffle = false;
+    Repeat m_repeat = RepeatOff;
+
+    QString m_accountName;
+    QString m_country;
+    Product m_product = UnknownProduct;
+
+    bool m_needsRestart = false;
 
     void onStopped(quint64 playRequestId, const QString& trackId);
     void onChanged(const QString& newTrackId);
@@ -144,16 +278,23 @@ private:
     void onVolumeChanged(quint16 value);
     void onConnecting();
     void onConnected();
+    void onLoginProgress(::LoginStage stage);
     void onError(SailifyErrorKind kind, const QString& message);
     void onShutdown();
     void onStartReconnect();
+    void onWaitingForDiscovery();
     void onTokenChanged(const QString& accessToken, quint32 expiresIn);
+    void onShuffleChanged(bool shuffle);
+    void onRepeatChanged(RepeatMode repeat);
+    void onSettingsApplied(const QString& applied, const QString& needsRestart);
+    void onAccountInfo(const QString& username, const QString& displayName, const QString& country, AccountProduct product);
 
     void setError(ErrorKind kind, const QString& message);
     void setPlayerStatus(
         const QString& trackId, qint32 positionMs, qint32 durationMs, MediaStatus mediaStatus,
         PlaybackState playbackState);
     void setConnectionStatus(ConnectionStatus value);
+    void updateNeedsRestart();
 };
 
 class SailifyPlayerCallback : public QObject {
@@ -175,10 +316,22 @@ signals:
     void volumeChanged(quint16 value);
     void connecting();
     void connected();
+    void loginProgress(::LoginStage stage);
+    void commandResult(quint64 request_id, const QString& command, SailifyCommandStatus status, const QString& message);
     void error(SailifyErrorKind kind, const QString& message);
     void shutdown();
     void startReconnect();
+    void waitingForDiscovery();
     void tokenChanged(const QString& access_token, quint32 expires_in);
+    void shuffleChanged(bool shuffle);
+    void repeatChanged(RepeatMode repeat);
+    void settingsApplied(const QString& applied, const QString& needs_restart);
+    void reconnectScheduled(quint32 attempt, quint64 delay_ms);
+    void stateChanged();
+    void position(quint32 position_ms);
+    void oauthCompleted();
+    void remoteLogin(const QString& username);
+    void accountInfo(const QString& username, const QString& display_name, const QString& country, AccountProduct product);
 
     void destroy();
 
@@ -192,10 +345,24 @@ private:
     static void onVolumeChanged(void *user_data, uint16_t value);
     static void onConnecting(void *user_data);
     static void onConnected(void *user_data);
+    static void onLoginProgress(void *user_data, ::LoginStage stage);
+    static void onCommandResult(
+        void *user_data, uint64_t request_id, SailifyStringView command, SailifyCommandStatus status,
+        SailifyStringView message);
     static void onError(void *user_data, SailifyErrorKind kind, SailifyStringView message);
     static void onShutdown(void *user_data);
     static void onStartReconnect(void *user_data);
+    static void onWaitingForDiscovery(void *user_data);
     static void onTokenChanged(void *user_data, SailifyStringView access_token, uint32_t expires_in);
+    static void onShuffleChanged(void *user_data, bool shuffle);
+    static void onRepeatChanged(void *user_data, RepeatMode repeat);
+    static void onSettingsApplied(void *user_data, SailifyStringView applied, SailifyStringView needs_restart);
+    static void onReconnectScheduled(void *user_data, uint32_t attempt, uint64_t delay_ms);
+    static void onStateChanged(void *user_data, const SailifyPlayerState *state);
+    static void onPosition(void *user_data, uint32_t position_ms);
+    static void onOAuthCompleted(void *user_data);
+    static void onRemoteLogin(void *user_data, SailifyStringView username);
+    static void onAccountInfo(void *user_data, SailifyStringView username, SailifyStringView display_name, SailifyStringView country, AccountProduct product);
 
     static void onDestroy(void *data);
 };
@@ -203,3 +370,7 @@ private:
 }
 
 Q_DECLARE_METATYPE(SailifyErrorKind)
+Q_DECLARE_METATYPE(RepeatMode)
+Q_DECLARE_METATYPE(AccountProduct)
+Q_DECLARE_METATYPE(::LoginStage)
+Q_DECLARE_METATYPE(SailifyCommandStatus)
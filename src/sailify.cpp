@@ -30,6 +30,10 @@ int main(int argc, char *argv[]) {
 
     qmlRegisterType<Sailify::SailifyPlayer>("Sailify", 0, 1, "SailifyPlayer");
     qRegisterMetaType<SailifyErrorKind>();
+    qRegisterMetaType<RepeatMode>();
+    qRegisterMetaType<AccountProduct>();
+    qRegisterMetaType<::LoginStage>();
+    qRegisterMetaType<SailifyCommandStatus>();
 
     JsonListModel::registerQmlType();
 
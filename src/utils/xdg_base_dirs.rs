@@ -11,6 +11,17 @@ pub fn config_home() -> PathBuf {
         .into()
 }
 
+#[must_use]
+pub fn data_home() -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .unwrap_or_else(|| {
+            let mut path = std::env::var_os("HOME").expect("no home dir");
+            path.push("/.local/share");
+            path
+        })
+        .into()
+}
+
 #[must_use]
 pub fn cache_home() -> PathBuf {
     std::env::var_os("XDG_CACHE_HOME")
@@ -0,0 +1,79 @@
+//! Async API of a player that isn't started, no Spotify account needed
+
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+use futures::StreamExt;
+use sailifyplayer::player::commands::CommandError;
+use sailifyplayer::player::events::{LibrespotEvent, LibrespotEventListener, COMMAND_EVENTS};
+use sailifyplayer::player::handle::PlayerHandle;
+
+/// Keeps profiles and credentials of the tests out of the home directory
+fn use_temp_dirs() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let dir = tempfile::tempdir().unwrap().into_path();
+        std::env::set_var("XDG_CONFIG_HOME", dir.join("config"));
+        std::env::set_var("XDG_DATA_HOME", dir.join("data"));
+        std::env::set_var("XDG_CACHE_HOME", dir.join("cache"));
+    });
+}
+
+#[tokio::test]
+async fn commands_fail_while_not_running() {
+    use_temp_dirs();
+    let handle = PlayerHandle::new();
+    let mut events = handle.events(COMMAND_EVENTS);
+
+    assert!(matches!(handle.play().await, Err(CommandError::NotRunning)));
+    let evt = tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .unwrap();
+    assert!(matches!(
+        evt,
+        Some(LibrespotEvent::CommandResult {
+            command: "play",
+            result: Err(CommandError::NotRunning),
+            ..
+        })
+    ));
+
+    handle.stop().await;
+    assert!(!handle.with_player(|player| player.is_running()));
+}
+
+/// Calls into the player from the listener, like the callbacks of the C API do
+struct ReentrantListener {
+    handle: Mutex<Option<PlayerHandle>>,
+    results: Mutex<Sender<bool>>,
+}
+
+impl LibrespotEventListener for ReentrantListener {
+    fn notify(&self, evt: LibrespotEvent) {
+        if let LibrespotEvent::CommandResult { .. } = evt {
+            if let Some(handle) = self.handle.lock().unwrap().take() {
+                let active = handle.with_player(|player| player.is_active());
+                let _ = self.results.lock().unwrap().send(active);
+            }
+        }
+    }
+}
+
+#[test]
+fn polled_listeners_can_call_into_the_player() {
+    use_temp_dirs();
+    let (tx, rx) = channel();
+    let listener = Arc::new(ReentrantListener {
+        handle: Mutex::new(None),
+        results: Mutex::new(tx),
+    });
+    let handle = PlayerHandle::with_listener(listener.clone());
+    *listener.handle.lock().unwrap() = Some(handle.clone());
+    handle.set_event_polling(true);
+
+    handle.with_player(|player| player.pause());
+    assert!(rx.try_recv().is_err());
+    assert!(handle.poll_events(usize::MAX) >= 1);
+    assert!(!rx.try_recv().unwrap());
+}